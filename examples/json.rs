@@ -0,0 +1,16 @@
+use lunatic_log::{debug, error, info, subscriber::fmt::FmtSubscriber, trace, warn, LevelFilter};
+
+fn main() {
+    // Initialize subscriber
+    lunatic_log::init(FmtSubscriber::new(LevelFilter::Trace).json());
+
+    // Log message
+    error!("Error");
+    warn!("Warn");
+    info!("Info");
+    debug!("Debug");
+    trace!("Trace");
+
+    // Wait for events to be handled
+    lunatic_log::flush();
+}
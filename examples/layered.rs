@@ -0,0 +1,22 @@
+use lunatic_log::{
+    debug, info,
+    subscriber::{fmt::FmtSubscriber, layer::Fields, Subscriber},
+    LevelFilter,
+};
+
+fn main() {
+    // Stack a filter and an enricher on a subscriber, in a single process
+    let subscriber = FmtSubscriber::new(LevelFilter::Trace)
+        .with(LevelFilter::Info)
+        .with(Fields::new().with("service", "example"));
+
+    // Initialize layered subscriber
+    lunatic_log::init(subscriber);
+
+    // Log messages
+    info!("Hello, {}", "World");
+    debug!("Not shown");
+
+    // Wait for events to be handled
+    lunatic_log::flush();
+}
@@ -16,6 +16,6 @@ fn main() {
     // Log message
     info!("Hello, {}", "World");
 
-    // Wait for events to propagate
-    lunatic::sleep(std::time::Duration::from_millis(50));
+    // Wait for events to be handled
+    lunatic_log::flush();
 }
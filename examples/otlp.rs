@@ -0,0 +1,51 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    time::Duration,
+};
+
+use lunatic::{net::TcpListener, spawn_link, Mailbox};
+use lunatic_log::{info, info_span, subscriber::otlp::OtlpSubscriber, warn, LevelFilter};
+
+fn main() {
+    // Start a stand-in for an OpenTelemetry collector, printing received requests
+    spawn_link!(|_mailbox: Mailbox<()>| {
+        let listener = TcpListener::bind("127.0.0.1:4318").unwrap();
+        while let Ok((stream, _)) = listener.accept() {
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                if let Some(len) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = len.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            println!("{}", String::from_utf8_lossy(&body));
+            let mut stream = reader.into_inner();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        }
+    });
+    lunatic::sleep(Duration::from_millis(100));
+
+    // Initialize subscriber
+    lunatic_log::init(
+        OtlpSubscriber::new("http://127.0.0.1:4318/v1/logs", LevelFilter::Info)
+            .with_service_name("otlp-example"),
+    );
+
+    // Log messages
+    let span = info_span!("request", request_id = 42);
+    let _guard = span.enter();
+    info!(user = "alice"; "Handling request");
+    warn!("Request took {}ms", 1200);
+
+    // Wait for the batch to be exported
+    lunatic_log::flush();
+}
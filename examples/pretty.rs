@@ -11,6 +11,6 @@ fn main() {
     debug!("Debug");
     trace!("Trace");
 
-    // Wait for events to propagate
-    lunatic::sleep(std::time::Duration::from_millis(50));
+    // Wait for events to be handled
+    lunatic_log::flush();
 }
@@ -0,0 +1,386 @@
+//! Bounding the events queued for a subscriber.
+//!
+//! A lunatic mailbox is unbounded, so a burst of events from many processes
+//! can grow the memory of the subscriber process without limit. The
+//! subscriber process moves messages from its mailbox into a queue as soon as
+//! they arrive, and a [`Backpressure`] policy decides what happens to events
+//! once the queue holds too many of them.
+//!
+//! Arriving events that are dropped and queued events that are replaced by
+//! newer ones are counted, and a summary event with the `lunatic_log` target
+//! is handled by the subscriber periodically, so that the loss is visible.
+//!
+//! # Example
+//!
+//! ```
+//! use lunatic_log::{
+//!     backpressure::{Backpressure, Overflow},
+//!     subscriber::fmt::FmtSubscriber,
+//!     LevelFilter,
+//! };
+//!
+//! lunatic_log::init_with_backpressure(
+//!     FmtSubscriber::new(LevelFilter::Info),
+//!     Backpressure::bounded(10_000, Overflow::DropOldest),
+//! );
+//! ```
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use lunatic::{Process, Tag};
+use serde::{Deserialize, Serialize};
+
+use crate::{Event, Level, Message, Metadata};
+
+const DEFAULT_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The policy of a subscriber process for events arriving faster than they
+/// are handled.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Backpressure {
+    max_depth: Option<usize>,
+    overflow: Overflow,
+    summary_interval: Duration,
+}
+
+/// What to do with an event arriving when the queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Overflow {
+    /// Drop the arriving event.
+    DropNewest,
+    /// Drop the oldest queued event, and queue the arriving event.
+    DropOldest,
+    /// Make the emitting process wait until the queue has room, holding back
+    /// its events meanwhile.
+    ///
+    /// Every event is acknowledged by the subscriber process, which slows
+    /// down emitting processes even when the queue isn't full.
+    Block,
+    /// Keep one of every `n` arriving events in place of the oldest queued
+    /// event, and drop the others.
+    Sample(u32),
+}
+
+impl Default for Backpressure {
+    fn default() -> Self {
+        Backpressure::unbounded()
+    }
+}
+
+impl Backpressure {
+    /// A policy queuing every event, which is the default.
+    pub fn unbounded() -> Self {
+        Backpressure {
+            max_depth: None,
+            overflow: Overflow::DropNewest,
+            summary_interval: DEFAULT_SUMMARY_INTERVAL,
+        }
+    }
+
+    /// A policy queuing up to `max_depth` events, and handling further events
+    /// according to `overflow`.
+    pub fn bounded(max_depth: usize, overflow: Overflow) -> Self {
+        Backpressure {
+            max_depth: Some(max_depth.max(1)),
+            overflow,
+            summary_interval: DEFAULT_SUMMARY_INTERVAL,
+        }
+    }
+
+    /// Sets how often the number of dropped events is reported, 10 seconds by default.
+    pub fn with_summary_interval(mut self, summary_interval: Duration) -> Self {
+        self.summary_interval = summary_interval;
+        self
+    }
+
+    /// Returns `true` if emitting processes need to wait for every event to
+    /// be acknowledged.
+    pub(crate) fn acknowledge(&self) -> bool {
+        self.max_depth.is_some() && self.overflow == Overflow::Block
+    }
+}
+
+/// The messages queued in a subscriber process.
+pub(crate) struct Queue {
+    backpressure: Backpressure,
+    messages: VecDeque<Message>,
+    events: usize,
+    blocked: VecDeque<(VecDeque<Event>, Process<()>, Tag)>,
+    arrived: u64,
+    dropped: u64,
+    replaced: u64,
+    last_summary: Instant,
+}
+
+impl Queue {
+    pub(crate) fn new(backpressure: Backpressure) -> Self {
+        Queue {
+            backpressure,
+            messages: VecDeque::new(),
+            events: 0,
+            blocked: VecDeque::new(),
+            arrived: 0,
+            dropped: 0,
+            replaced: 0,
+            last_summary: Instant::now(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    fn is_full(&self) -> bool {
+        self.backpressure
+            .max_depth
+            .is_some_and(|max_depth| self.events >= max_depth)
+    }
+
+    /// Queues a message, applying the policy to events.
+    pub(crate) fn push(&mut self, message: Message) {
+        match message {
+            Message::Event(event) => self.push_event(event),
+            Message::BlockingEvent(event, proc, tag) => self.push_blocking(vec![event], proc, tag),
+            Message::Events(events) => {
+                for event in events {
+                    self.push_event(event);
+                }
+            }
+            Message::BlockingEvents(events, proc, tag) => self.push_blocking(events, proc, tag),
+            message => self.messages.push_back(message),
+        }
+    }
+
+    /// Queues events whose sender waits until the queue has room for them.
+    fn push_blocking(&mut self, events: Vec<Event>, proc: Process<()>, tag: Tag) {
+        self.blocked.push_back((events.into(), proc, tag));
+        self.admit_blocked();
+    }
+
+    /// Moves the events of blocked senders into the queue while it has room,
+    /// letting a sender continue once all of its events are queued.
+    fn admit_blocked(&mut self) {
+        while let Some((events, _, _)) = self.blocked.front_mut() {
+            while !self.is_full() {
+                match events.pop_front() {
+                    Some(event) => {
+                        self.messages.push_back(Message::Event(event));
+                        self.events += 1;
+                    }
+                    None => break,
+                }
+            }
+            if !events.is_empty() {
+                return;
+            }
+            if let Some((_, proc, tag)) = self.blocked.pop_front() {
+                proc.tag_send(tag, ());
+            }
+        }
+    }
+
+    fn push_event(&mut self, event: Event) {
+        self.arrived = self.arrived.wrapping_add(1);
+        if self.is_full() {
+            let replace_oldest = match self.backpressure.overflow {
+                Overflow::DropOldest => true,
+                Overflow::Sample(n) => self.arrived % n.max(1) as u64 == 0,
+                Overflow::DropNewest | Overflow::Block => false,
+            };
+            if !replace_oldest {
+                self.dropped += 1;
+                return;
+            }
+            let oldest = self
+                .messages
+                .iter()
+                .position(|message| matches!(message, Message::Event(_)));
+            if let Some(oldest) = oldest {
+                self.messages.remove(oldest);
+                self.events -= 1;
+                self.replaced += 1;
+            }
+        }
+        self.messages.push_back(Message::Event(event));
+        self.events += 1;
+    }
+
+    /// Removes the oldest message, queuing the events of blocked processes if
+    /// there is room.
+    pub(crate) fn pop(&mut self) -> Option<Message> {
+        let message = self.messages.pop_front()?;
+        if let Message::Event(_) = message {
+            self.events -= 1;
+            self.admit_blocked();
+        }
+        Some(message)
+    }
+
+    /// Returns how long to wait for messages before a summary is due, if any
+    /// events were dropped or replaced.
+    pub(crate) fn summary_timeout(&self) -> Option<Duration> {
+        (self.dropped > 0 || self.replaced > 0).then(|| {
+            self.backpressure
+                .summary_interval
+                .saturating_sub(self.last_summary.elapsed())
+        })
+    }
+
+    /// Returns an event reporting the dropped and replaced events, if any
+    /// were lost and the summary is due or `force` is set.
+    pub(crate) fn summary(&mut self, force: bool) -> Option<Event> {
+        if (self.dropped == 0 && self.replaced == 0)
+            || (!force && self.last_summary.elapsed() < self.backpressure.summary_interval)
+        {
+            return None;
+        }
+        let dropped = std::mem::take(&mut self.dropped);
+        let replaced = std::mem::take(&mut self.replaced);
+        self.last_summary = Instant::now();
+        let metadata = Metadata::new(
+            "dropped events".to_string(),
+            "lunatic_log".to_string(),
+            Level::Warn,
+            vec!["dropped".to_string(), "replaced".to_string()],
+            Some(module_path!().to_string()),
+            None,
+            None,
+        );
+        let message = format!(
+            "{dropped} events dropped and {replaced} queued events replaced by newer events \
+             because the subscriber queue was full"
+        );
+        Some(Event::new(
+            message,
+            metadata,
+            vec![dropped.into(), replaced.into()],
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lunatic::{Mailbox, MailboxResult};
+
+    use super::*;
+    use crate::Value;
+
+    fn event(message: &str) -> Event {
+        let metadata = Metadata::new(
+            "event".to_string(),
+            "my_app".to_string(),
+            Level::Info,
+            Vec::new(),
+            None,
+            None,
+            None,
+        );
+        Event::new(message.to_string(), metadata, Vec::new())
+    }
+
+    fn push_events(queue: &mut Queue, count: usize) {
+        for i in 1..=count {
+            queue.push(Message::Event(event(&i.to_string())));
+        }
+    }
+
+    fn queued(queue: &mut Queue) -> Vec<String> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|message| match message {
+                Message::Event(event) => event.message().clone(),
+                _ => panic!("expected an event"),
+            })
+            .collect()
+    }
+
+    fn lost(queue: &mut Queue) -> (u64, u64) {
+        let Some(summary) = queue.summary(true) else {
+            return (0, 0);
+        };
+        match (summary.field("dropped"), summary.field("replaced")) {
+            (Some(Value::U64(dropped)), Some(Value::U64(replaced))) => (*dropped, *replaced),
+            fields => panic!("unexpected summary fields {fields:?}"),
+        }
+    }
+
+    /// Returns `true` if the subscriber acknowledged the events sent with `tag`.
+    fn acknowledged(tag: Tag) -> bool {
+        // Safety: only the acknowledgement carrying `tag` is received, which
+        // is always a `()`.
+        let mailbox: Mailbox<()> = unsafe { Mailbox::new() };
+        matches!(
+            mailbox.tag_receive_timeout(&[tag], Duration::ZERO),
+            MailboxResult::Message(())
+        )
+    }
+
+    #[test]
+    fn unbounded_queues_every_event() {
+        let mut queue = Queue::new(Backpressure::unbounded());
+        push_events(&mut queue, 100);
+        assert_eq!(queued(&mut queue).len(), 100);
+        assert_eq!(lost(&mut queue), (0, 0));
+    }
+
+    #[test]
+    fn drop_newest_drops_arriving_events() {
+        let mut queue = Queue::new(Backpressure::bounded(2, Overflow::DropNewest));
+        push_events(&mut queue, 5);
+        assert_eq!(queued(&mut queue), ["1", "2"]);
+        assert_eq!(lost(&mut queue), (3, 0));
+    }
+
+    #[test]
+    fn drop_oldest_replaces_queued_events() {
+        let mut queue = Queue::new(Backpressure::bounded(2, Overflow::DropOldest));
+        push_events(&mut queue, 5);
+        assert_eq!(queued(&mut queue), ["4", "5"]);
+        assert_eq!(lost(&mut queue), (0, 3));
+    }
+
+    #[test]
+    fn sample_keeps_one_of_every_n_events() {
+        let mut queue = Queue::new(Backpressure::bounded(2, Overflow::Sample(3)));
+        push_events(&mut queue, 9);
+        assert_eq!(queued(&mut queue), ["6", "9"]);
+        assert_eq!(lost(&mut queue), (4, 3));
+    }
+
+    #[test]
+    fn keeps_other_messages_when_full() {
+        let mut queue = Queue::new(Backpressure::bounded(1, Overflow::DropOldest));
+        queue.push(Message::Event(event("1")));
+        queue.push(Message::Flush(Process::this(), Tag::new()));
+        queue.push(Message::Event(event("2")));
+        assert!(matches!(queue.pop(), Some(Message::Flush(..))));
+        assert_eq!(queued(&mut queue), ["2"]);
+        assert_eq!(lost(&mut queue), (0, 1));
+    }
+
+    #[test]
+    fn block_holds_back_events_until_there_is_room() {
+        let mut queue = Queue::new(Backpressure::bounded(2, Overflow::Block));
+        let first = Tag::new();
+        let second = Tag::new();
+        let events = vec![event("1"), event("2"), event("3")];
+        queue.push(Message::BlockingEvents(events, Process::this(), first));
+        queue.push(Message::BlockingEvent(event("4"), Process::this(), second));
+        assert_eq!(queue.events, 2);
+        assert!(!acknowledged(first));
+
+        assert!(queue.pop().is_some());
+        assert_eq!(queue.events, 2);
+        assert!(acknowledged(first));
+        assert!(!acknowledged(second));
+
+        assert!(queue.pop().is_some());
+        assert_eq!(queue.events, 2);
+        assert!(acknowledged(second));
+
+        assert_eq!(queued(&mut queue), ["3", "4"]);
+        assert_eq!(lost(&mut queue), (0, 0));
+    }
+}
@@ -0,0 +1,201 @@
+//! Batched delivery of events from the current process.
+//!
+//! Every event is normally sent to the subscriber as its own message. With
+//! buffering [enabled](enable), the events of the current process are
+//! collected and sent as a single batch when:
+//! - the buffer holds the maximum number of events,
+//! - an event is emitted, or a span is entered or exited, after the buffer
+//!   reached its maximum age,
+//! - an [`Error`](crate::Level::Error) event is emitted,
+//! - the process creates or closes a span, so that the subscriber knows of the
+//!   spans of the events it handles,
+//! - [`flush`](crate::flush), [`shutdown`](crate::shutdown) or [`disable`] is
+//!   called.
+//!
+//! Entering and exiting a span doesn't send a younger buffer, since that would
+//! send a batch for every few events. The subscriber can therefore see a span
+//! being entered or exited before events emitted earlier, which still carry
+//! the spans entered when they were emitted.
+//!
+//! Buffered events are lost if the process exits before they are sent, so
+//! processes should call [`flush`](crate::flush) before exiting.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use lunatic_log::{buffer::{self, Buffering}, info};
+//!
+//! # fn main() {
+//! buffer::enable(Buffering::new().with_max_events(128).with_max_age(Duration::from_millis(500)));
+//!
+//! for i in 0..1000 {
+//!     info!("Event {}", i);
+//! }
+//!
+//! lunatic_log::flush();
+//! # }
+//! ```
+
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+use lunatic::{process_local, Process};
+
+use crate::{Event, Level, Message};
+
+const DEFAULT_MAX_EVENTS: usize = 64;
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(1);
+
+process_local! {
+    static BUFFER: RefCell<Option<Buffer>> = RefCell::new(None);
+}
+
+/// The limits of the event buffer of a process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Buffering {
+    max_events: usize,
+    max_age: Duration,
+}
+
+impl Default for Buffering {
+    fn default() -> Self {
+        Buffering::new()
+    }
+}
+
+impl Buffering {
+    /// Creates buffering limits of 64 events and 1 second.
+    pub fn new() -> Self {
+        Buffering {
+            max_events: DEFAULT_MAX_EVENTS,
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// Sends the buffer once it holds this many events.
+    pub fn with_max_events(mut self, max_events: usize) -> Self {
+        self.max_events = max_events.max(1);
+        self
+    }
+
+    /// Sends the buffer when an event is emitted, or a span is entered or
+    /// exited, this long after the first buffered event.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+}
+
+struct Buffer {
+    buffering: Buffering,
+    proc: Option<Process<Message>>,
+    events: Vec<Event>,
+    started: Instant,
+}
+
+/// Enables buffering of the events emitted by the current process, replacing
+/// the limits if it is already enabled.
+pub fn enable(buffering: Buffering) {
+    BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        match &mut *buffer {
+            Some(buffer) => buffer.buffering = buffering,
+            None => {
+                *buffer = Some(Buffer {
+                    buffering,
+                    proc: None,
+                    events: Vec::new(),
+                    started: Instant::now(),
+                })
+            }
+        }
+    });
+}
+
+/// Sends the buffered events and disables buffering in the current process.
+pub fn disable() {
+    send_buffered();
+    BUFFER.with(|buffer| *buffer.borrow_mut() = None);
+}
+
+/// Returns `true` if buffering is enabled in the current process.
+pub fn is_enabled() -> bool {
+    BUFFER.with(|buffer| buffer.borrow().is_some())
+}
+
+/// Buffers an event for `proc`, returning it if buffering is disabled.
+pub(crate) fn push(proc: &Process<Message>, event: Event) -> Option<Event> {
+    let batch = BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        let buffer = match &mut *buffer {
+            Some(buffer) => buffer,
+            None => return Err(event),
+        };
+        let mut batches = Vec::new();
+        if let Some(buffered_proc) = &buffer.proc {
+            if buffered_proc.id() != proc.id() {
+                batches.push(buffer.take());
+            }
+        }
+        if buffer.events.is_empty() {
+            buffer.proc = Some(proc.clone());
+            buffer.started = Instant::now();
+        }
+        let send = *event.metadata().level() == Level::Error
+            || buffer.events.len() + 1 >= buffer.buffering.max_events
+            || buffer.started.elapsed() >= buffer.buffering.max_age;
+        buffer.events.push(event);
+        if send {
+            batches.push(buffer.take());
+        }
+        Ok(batches)
+    });
+    match batch {
+        Ok(batches) => {
+            // Sent outside of the borrow, since sending may wait for the subscriber.
+            for (proc, events) in batches.into_iter().flatten() {
+                crate::send_events(&proc, events);
+            }
+            None
+        }
+        Err(event) => Some(event),
+    }
+}
+
+/// Sends the buffered events, if any.
+pub(crate) fn send_buffered() {
+    let batch = BUFFER.with(|buffer| buffer.borrow_mut().as_mut().and_then(Buffer::take));
+    if let Some((proc, events)) = batch {
+        crate::send_events(&proc, events);
+    }
+}
+
+/// Sends the buffered events, if the buffer reached its maximum age.
+pub(crate) fn send_expired() {
+    let batch = BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        let buffer = buffer.as_mut()?;
+        if buffer.started.elapsed() < buffer.buffering.max_age {
+            return None;
+        }
+        buffer.take()
+    });
+    if let Some((proc, events)) = batch {
+        crate::send_events(&proc, events);
+    }
+}
+
+impl Buffer {
+    /// Takes the buffered events and the process they are sent to.
+    fn take(&mut self) -> Option<(Process<Message>, Vec<Event>)> {
+        if self.events.is_empty() {
+            return None;
+        }
+        let proc = self.proc.take()?;
+        Some((proc, std::mem::take(&mut self.events)))
+    }
+}
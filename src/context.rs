@@ -0,0 +1,114 @@
+//! Process-local logging context.
+//!
+//! The context is a small key-value map attached to every [`Event`](crate::Event)
+//! emitted by the current process, such as a `request_id` or `tenant`. It is
+//! carried into child processes spawned with
+//! [`spawn_link_with_context`](crate::spawn_link_with_context).
+//!
+//! # Example
+//!
+//! ```
+//! use lunatic_log::{context, info, spawn_link_with_context};
+//!
+//! # fn main() {
+//! context::insert("request_id", 42);
+//!
+//! // Logged with `request_id=42`
+//! info!("Handling request");
+//!
+//! spawn_link_with_context!(|_mailbox: Mailbox<()>| {
+//!     // Also logged with `request_id=42`
+//!     info!("Working on request");
+//! });
+//! # }
+//! ```
+
+use std::collections::{btree_map, BTreeMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Value, CONTEXT};
+
+/// A key-value map of logging context.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Context {
+    fields: BTreeMap<String, Value>,
+}
+
+impl Context {
+    /// Creates an empty [`Context`].
+    pub const fn new() -> Self {
+        Context {
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Inserts a value, returning the previous value for the key.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+        self.fields.insert(key.into(), value.into())
+    }
+
+    /// Removes a value, returning it if the key was present.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.fields.remove(key)
+    }
+
+    /// Returns the value for a key.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.fields.get(key)
+    }
+
+    /// Returns an iterator over the keys and values, ordered by key.
+    pub fn iter(&self) -> btree_map::Iter<'_, String, Value> {
+        self.fields.iter()
+    }
+
+    /// Returns the number of values in the context.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns `true` if the context contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Context {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = btree_map::Iter<'a, String, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Inserts a value into the context of the current process.
+pub fn insert(key: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+    CONTEXT.with(|context| context.borrow_mut().insert(key, value))
+}
+
+/// Removes a value from the context of the current process.
+pub fn remove(key: &str) -> Option<Value> {
+    CONTEXT.with(|context| context.borrow_mut().remove(key))
+}
+
+/// Returns a value from the context of the current process.
+pub fn get(key: &str) -> Option<Value> {
+    CONTEXT.with(|context| context.borrow().get(key).cloned())
+}
+
+/// Removes all values from the context of the current process.
+pub fn clear() {
+    CONTEXT.with(|context| *context.borrow_mut() = Context::new());
+}
+
+/// Returns a copy of the context of the current process.
+pub fn current() -> Context {
+    CONTEXT.with(|context| context.borrow().clone())
+}
+
+/// Replaces the context of the current process.
+pub fn set(new_context: Context) {
+    CONTEXT.with(|context| *context.borrow_mut() = new_context);
+}
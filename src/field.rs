@@ -0,0 +1,147 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A typed value recorded in a field of an [Event](super::Event).
+///
+/// Fields are added to events with the `key = value` syntax of the logging
+/// macros. Values are converted with [`From`], or captured with their
+/// [`fmt::Debug`] or [`fmt::Display`] implementation using the `key = ?value`
+/// and `key = %value` syntax respectively.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::info;
+///
+/// # fn main() {
+/// let user = "alice";
+/// let path = std::path::Path::new("/index.html");
+///
+/// info!(request_id = 42, user = user, path = ?path, ok = true; "Request handled");
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    /// A string value.
+    Str(String),
+    /// A signed integer value.
+    I64(i64),
+    /// An unsigned integer value.
+    U64(u64),
+    /// A floating point value.
+    F64(f64),
+    /// A boolean value.
+    Bool(bool),
+    /// A value captured with its [`fmt::Debug`] implementation.
+    Debug(String),
+    /// A value captured with its [`fmt::Display`] implementation.
+    Display(String),
+}
+
+impl Value {
+    /// Captures a value using its [`fmt::Debug`] implementation.
+    pub fn debug<T: fmt::Debug + ?Sized>(value: &T) -> Self {
+        Value::Debug(format!("{value:?}"))
+    }
+
+    /// Captures a value using its [`fmt::Display`] implementation.
+    pub fn display<T: fmt::Display + ?Sized>(value: &T) -> Self {
+        Value::Display(value.to_string())
+    }
+
+    /// Returns the value as a string slice if it is a string, or was captured
+    /// with [`fmt::Debug`] or [`fmt::Display`].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) | Value::Debug(s) | Value::Display(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an `i64` if it is an integer that fits.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::I64(n) => Some(n),
+            Value::U64(n) => i64::try_from(n).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a `u64` if it is an integer that fits.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::I64(n) => u64::try_from(n).ok(),
+            Value::U64(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an `f64` if it is a float.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::F64(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a `bool` if it is a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) | Value::Debug(s) | Value::Display(s) => f.write_str(s),
+            Value::I64(n) => write!(f, "{n}"),
+            Value::U64(n) => write!(f, "{n}"),
+            Value::F64(n) => write!(f, "{n}"),
+            Value::Bool(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($variant:ident($as:ty): $($ty:ty),+) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(value: $ty) -> Self {
+                    Value::$variant(value as $as)
+                }
+            }
+        )+
+    };
+}
+
+impl_from!(I64(i64): i8, i16, i32, i64, isize);
+impl_from!(U64(u64): u8, u16, u32, u64, usize);
+impl_from!(F64(f64): f32, f64);
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl From<&String> for Value {
+    fn from(value: &String) -> Self {
+        Value::Str(value.clone())
+    }
+}
@@ -0,0 +1,334 @@
+//! Filter events with directives in the `RUST_LOG` syntax.
+//!
+//! # Syntax
+//!
+//! A filter is a comma-separated list of directives. Each directive is either
+//! a level, which sets the default level, a target, which enables all levels
+//! for it, or a `target=level` pair. A directive applies to its target and
+//! submodules, and is matched against both [`Metadata::target`] and
+//! [`Metadata::module_path`]. When multiple directives match, the one with the
+//! longest target takes precedence. Events matching no directive use the
+//! default level, which is [`LevelFilter::Error`] unless set.
+//!
+//! For example `info,my_app::db=debug,hyper=off` enables `info` events
+//! everywhere, `debug` events in `my_app::db`, and no events in `hyper`.
+
+use std::{env, error, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    subscriber::{target_matches, Interest},
+    LevelFilter, Metadata,
+};
+
+/// The environment variable read by [`EnvFilter::from_default_env`].
+pub const DEFAULT_ENV: &str = "RUST_LOG";
+
+/// A filter enabling events based on directives.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{filter::EnvFilter, subscriber::fmt::FmtSubscriber, LevelFilter};
+///
+/// let filter: EnvFilter = "info,my_app::db=debug,hyper=off".parse().unwrap();
+/// lunatic_log::init(FmtSubscriber::new(LevelFilter::Info).with_env_filter(filter));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvFilter {
+    default: LevelFilter,
+    directives: Vec<Directive>,
+}
+
+impl Default for EnvFilter {
+    fn default() -> Self {
+        EnvFilter {
+            default: LevelFilter::Error,
+            directives: Vec::new(),
+        }
+    }
+}
+
+impl EnvFilter {
+    /// Creates a filter from the directives in an environment variable.
+    ///
+    /// An empty filter is returned if the variable is not set.
+    pub fn from_env(var: &str) -> Result<Self, ParseFilterError> {
+        match env::var(var) {
+            Ok(directives) => directives.parse(),
+            Err(_) => Ok(EnvFilter::default()),
+        }
+    }
+
+    /// Creates a filter from the directives in the `RUST_LOG` environment variable.
+    pub fn from_default_env() -> Result<Self, ParseFilterError> {
+        EnvFilter::from_env(DEFAULT_ENV)
+    }
+
+    /// Sets the level of events matching no directive.
+    pub fn with_default(mut self, default: LevelFilter) -> Self {
+        self.default = default;
+        self
+    }
+
+    /// Adds a directive, replacing any directive with the same target.
+    pub fn add_directive(mut self, directive: Directive) -> Self {
+        match self
+            .directives
+            .iter_mut()
+            .find(|d| d.target == directive.target)
+        {
+            Some(existing) => *existing = directive,
+            None => self.directives.push(directive),
+        }
+        self
+    }
+
+    /// Returns the directives of the filter.
+    pub fn directives(&self) -> &[Directive] {
+        &self.directives
+    }
+
+    /// Returns the level enabled for a target and module path.
+    pub fn level_for(&self, target: &str, module_path: Option<&str>) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter(|d| d.matches(target) || module_path.is_some_and(|m| d.matches(m)))
+            .max_by_key(|d| d.target.len())
+            .map(|d| d.level)
+            .unwrap_or(self.default)
+    }
+
+    /// Returns `true` if an event or span with the given [`Metadata`] is enabled.
+    pub fn enabled(&self, metadata: &Metadata) -> bool {
+        let module_path = metadata.module_path().map(String::as_str);
+        metadata.level() <= &self.level_for(metadata.target(), module_path)
+    }
+
+    /// Returns the most verbose level enabled by the filter.
+    pub fn max_level_hint(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|d| d.level)
+            .fold(self.default, std::cmp::max)
+    }
+
+    /// Returns the [`Interest`] of a subscriber using this filter.
+    pub fn interest(&self) -> Interest {
+        self.directives
+            .iter()
+            .fold(Interest::max_level(self.default), |interest, d| {
+                interest.with_target(d.target.clone(), d.level)
+            })
+    }
+}
+
+impl From<LevelFilter> for EnvFilter {
+    fn from(level: LevelFilter) -> Self {
+        EnvFilter::default().with_default(level)
+    }
+}
+
+impl FromStr for EnvFilter {
+    type Err = ParseFilterError;
+
+    fn from_str(directives: &str) -> Result<Self, Self::Err> {
+        let mut filter = EnvFilter::default();
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            if let Ok(level) = directive.parse::<LevelFilter>() {
+                filter.default = level;
+            } else {
+                filter = filter.add_directive(directive.parse()?);
+            }
+        }
+        Ok(filter)
+    }
+}
+
+impl fmt::Display for EnvFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_lowercase())?;
+        for directive in &self.directives {
+            write!(f, ",{directive}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A directive enabling a level for a target and its submodules.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Directive {
+    target: String,
+    level: LevelFilter,
+}
+
+impl Directive {
+    /// Creates a directive for a target and level.
+    pub fn new(target: impl Into<String>, level: LevelFilter) -> Self {
+        Directive {
+            target: target.into(),
+            level,
+        }
+    }
+
+    /// Returns the target of the directive.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the level of the directive.
+    pub fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    /// Returns `true` if the directive applies to the target or module path.
+    pub fn matches(&self, target: &str) -> bool {
+        target_matches(&self.target, target)
+    }
+}
+
+impl FromStr for Directive {
+    type Err = ParseFilterError;
+
+    fn from_str(directive: &str) -> Result<Self, Self::Err> {
+        let error = || ParseFilterError(directive.to_string());
+        let (target, level) = match directive.split_once('=') {
+            Some((target, level)) => (target.trim(), level.trim().parse().map_err(|_| error())?),
+            None => (directive.trim(), LevelFilter::max()),
+        };
+        if target.is_empty() || target.contains(char::is_whitespace) {
+            return Err(error());
+        }
+        Ok(Directive::new(target, level))
+    }
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.target, self.level.as_str().to_lowercase())
+    }
+}
+
+/// The type returned when a filter directive can't be parsed.
+#[derive(Debug, PartialEq)]
+pub struct ParseFilterError(String);
+
+impl fmt::Display for ParseFilterError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid filter directive `{}`", self.0)
+    }
+}
+
+impl error::Error for ParseFilterError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Level;
+
+    fn metadata(target: &str, level: Level, module_path: Option<&str>) -> Metadata {
+        Metadata::new(
+            "event".to_string(),
+            target.to_string(),
+            level,
+            Vec::new(),
+            module_path.map(str::to_string),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn bare_level_sets_default() {
+        let filter: EnvFilter = "debug".parse().unwrap();
+        assert_eq!(filter, EnvFilter::from(LevelFilter::Debug));
+        assert_eq!(filter.level_for("my_app", None), LevelFilter::Debug);
+        assert_eq!(
+            EnvFilter::default().level_for("my_app", None),
+            LevelFilter::Error
+        );
+    }
+
+    #[test]
+    fn target_level_applies_to_submodules() {
+        let filter: EnvFilter = "my_app=warn".parse().unwrap();
+        assert_eq!(filter.level_for("my_app", None), LevelFilter::Warn);
+        assert_eq!(filter.level_for("my_app::db", None), LevelFilter::Warn);
+        assert_eq!(filter.level_for("my_apps", None), LevelFilter::Error);
+        assert_eq!(
+            filter.level_for("custom", Some("my_app::db")),
+            LevelFilter::Warn
+        );
+    }
+
+    #[test]
+    fn bare_target_enables_all_levels() {
+        let filter: EnvFilter = "my_app".parse().unwrap();
+        assert_eq!(filter.level_for("my_app::db", None), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn longest_prefix_takes_precedence() {
+        for directives in [
+            "my_app=warn,my_app::db=trace",
+            "my_app::db=trace,my_app=warn",
+        ] {
+            let filter: EnvFilter = directives.parse().unwrap();
+            assert_eq!(
+                filter.level_for("my_app::db::pool", None),
+                LevelFilter::Trace
+            );
+            assert_eq!(filter.level_for("my_app::http", None), LevelFilter::Warn);
+        }
+    }
+
+    #[test]
+    fn later_directive_replaces_same_target() {
+        let filter: EnvFilter = "my_app=warn,my_app=debug".parse().unwrap();
+        assert_eq!(
+            filter.directives(),
+            [Directive::new("my_app", LevelFilter::Debug)]
+        );
+    }
+
+    #[test]
+    fn off_disables_target() {
+        let filter: EnvFilter = "info,hyper=off".parse().unwrap();
+        assert_eq!(filter.level_for("hyper::client", None), LevelFilter::Off);
+        assert!(!filter.enabled(&metadata("hyper::client", Level::Error, None)));
+        assert!(filter.enabled(&metadata("my_app", Level::Info, None)));
+        assert!(!filter.enabled(&metadata("my_app", Level::Debug, None)));
+    }
+
+    #[test]
+    fn malformed_directives_are_rejected() {
+        for directives in ["=debug", "my_app=loud", "my app=info", "info,my_app=", "="] {
+            assert!(
+                directives.parse::<EnvFilter>().is_err(),
+                "`{directives}` should be rejected"
+            );
+        }
+        assert_eq!(
+            "=debug".parse::<Directive>(),
+            Err(ParseFilterError("=debug".to_string()))
+        );
+    }
+
+    #[test]
+    fn whitespace_and_empty_segments_are_ignored() {
+        let filter: EnvFilter = " info , ,my_app = debug ,,".parse().unwrap();
+        assert_eq!(filter, "info,my_app=debug".parse().unwrap());
+        assert_eq!("".parse::<EnvFilter>().unwrap(), EnvFilter::default());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let filter: EnvFilter = "info,my_app::db=debug,hyper=off".parse().unwrap();
+        assert_eq!(filter.to_string(), "info,my_app::db=debug,hyper=off");
+        assert_eq!(filter.to_string().parse::<EnvFilter>().unwrap(), filter);
+    }
+}
@@ -355,6 +355,33 @@ impl LevelFilter {
     }
 }
 
+/// The statically resolved maximum log level.
+///
+/// Events above this level are removed at compile time by the logging macros.
+/// It is set with the `max_level_*` cargo features, or the `release_max_level_*`
+/// features in builds without debug assertions, and defaults to [`LevelFilter::Trace`].
+///
+/// # Example
+///
+/// ```toml
+/// [dependencies]
+/// lunatic-log = { version = "0.5", features = ["max_level_debug", "release_max_level_info"] }
+/// ```
+pub const STATIC_MAX_LEVEL: LevelFilter = match cfg!(debug_assertions) {
+    false if cfg!(feature = "release_max_level_off") => LevelFilter::Off,
+    false if cfg!(feature = "release_max_level_error") => LevelFilter::Error,
+    false if cfg!(feature = "release_max_level_warn") => LevelFilter::Warn,
+    false if cfg!(feature = "release_max_level_info") => LevelFilter::Info,
+    false if cfg!(feature = "release_max_level_debug") => LevelFilter::Debug,
+    false if cfg!(feature = "release_max_level_trace") => LevelFilter::Trace,
+    _ if cfg!(feature = "max_level_off") => LevelFilter::Off,
+    _ if cfg!(feature = "max_level_error") => LevelFilter::Error,
+    _ if cfg!(feature = "max_level_warn") => LevelFilter::Warn,
+    _ if cfg!(feature = "max_level_info") => LevelFilter::Info,
+    _ if cfg!(feature = "max_level_debug") => LevelFilter::Debug,
+    _ => LevelFilter::Trace,
+};
+
 /// The type returned by [`from_str`] when the string doesn't match any of the log levels.
 ///
 /// [`from_str`]: https://doc.rust-lang.org/std/str/trait.FromStr.html#tymethod.from_str
@@ -2,6 +2,24 @@
 //!
 //! A [`Subscriber`] is initialized in a [`lunatic::Process`] with [`init`].
 //! Logs are emitted to the subscriber when the [`error`], [`warn`], [`info`], [`debug`], [`trace`] macros are used.
+//! Events can be grouped into [`Span`]s with the [`span`] macro.
+//!
+//! Panics can be logged through the subscriber with [`install_panic_hook`].
+//!
+//! The events queued for the subscriber can be bounded with a
+//! [`Backpressure`] policy, see [`init_with_backpressure`]. Chatty processes
+//! can send their events in batches with [`buffer::enable`].
+//!
+//! # Compile time filters
+//!
+//! Events above a level can be removed at compile time with the `max_level_*`
+//! and `release_max_level_*` cargo features. See [`STATIC_MAX_LEVEL`].
+//!
+//! # Bridges
+//!
+//! With the `log` and `tracing` features, records of the `log` crate and
+//! events of the `tracing` crate can be sent to the subscriber. See the
+//! [`log`](mod@log) and [`tracing`](mod@tracing) modules.
 //!
 //! # Example
 //!
@@ -17,72 +35,473 @@
 
 #![deny(missing_docs)]
 
+pub mod backpressure;
+pub mod buffer;
+pub mod context;
+mod field;
+pub mod filter;
 mod level;
+#[cfg(feature = "log")]
+pub mod log;
 #[macro_use]
 mod macros;
 mod metadata;
+mod panic;
+mod span;
 pub mod subscriber;
+#[cfg(feature = "tracing")]
+pub mod tracing;
 
 use std::cell::RefCell;
+use std::time::{Duration, Instant, SystemTime};
 
+use backpressure::{Backpressure, Queue};
+use context::Context;
+use filter::EnvFilter;
 use lunatic::ProcessName;
-use lunatic::{process_local, spawn_link, Process};
+use lunatic::{process_local, spawn_link, Mailbox, MailboxResult, Process, Tag};
 use serde::{Deserialize, Serialize};
-use subscriber::Subscriber;
+use subscriber::{Interest, Subscriber};
 
+pub use crate::field::*;
 pub use crate::level::*;
 pub use crate::metadata::*;
+pub use crate::panic::install_panic_hook;
+pub use crate::span::*;
+
+// This is re-exported for use in macros, and is not part of the public API.
+#[doc(hidden)]
+pub use lunatic as __lunatic;
 
 process_local! {
     static LOGGING_PROCESS: RefCell<LoggingProcess> = RefCell::new(LoggingProcess::NotLookedUp);
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::new());
 }
 
 #[derive(ProcessName)]
 struct LoggingProcessID;
 
+#[derive(ProcessName)]
+struct InterestProcessID;
+
+/// How often emitting processes check the registry for a new subscriber or
+/// [`Interest`], so that changes made with [`set_filter`] reach them.
+///
+/// Checking only looks up names in the registry. Emitting processes ask the
+/// interest process for the [`Interest`] only when it was replaced.
+const INTEREST_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait for the subscriber to reply to a request, such as
+/// [`flush`], before giving up.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
 enum LoggingProcess {
     NotLookedUp,
     NotPresent,
-    Present(Process<Event>),
+    Present {
+        process: Process<Message>,
+        published: Published,
+        /// The id of the interest process `published` was received from.
+        interest_id: Option<u64>,
+        checked_at: Instant,
+    },
+}
+
+/// The state of the subscriber published to emitting processes.
+#[derive(Clone, Serialize, Deserialize)]
+struct Published {
+    interest: Interest,
+    /// Whether events need to be acknowledged, see [`Overflow::Block`](backpressure::Overflow::Block).
+    acknowledge: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+enum InterestMessage {
+    Get(Process<Published>, Tag),
+    /// Sent when a new interest process took over. The replaced process keeps
+    /// answering with the new state until emitting processes noticed it.
+    Replace(Published),
+    Stop,
+}
+
+/// The interest process of a subscriber initialized with [`init`].
+#[derive(Serialize, Deserialize)]
+struct Publisher {
+    process: Process<InterestMessage>,
+    published: Published,
+}
+
+impl Publisher {
+    /// Publishes a new [`Interest`] through a new interest process, so that
+    /// emitting processes notice the change from its id in the registry.
+    fn publish(&mut self, interest: Interest) {
+        self.published.interest = interest;
+        let process = spawn_interest(self.published.clone());
+        process.register(&InterestProcessID);
+        self.process
+            .send(InterestMessage::Replace(self.published.clone()));
+        self.process = process;
+    }
+
+    /// Frees the registered names, so that `init` can be called again, and
+    /// stops the interest process.
+    fn stop(&self) {
+        unregister(&LoggingProcessID);
+        unregister(&InterestProcessID);
+        self.process.send(InterestMessage::Stop);
+    }
 }
 
 /// Initialize a subscriber to handle log events.
 ///
 /// The subscriber is spawned in a [`lunatic::Process`] and receives log events.
-pub fn init(subscriber: impl Subscriber) -> Process<Event> {
-    if Process::<Event>::lookup(&LoggingProcessID).is_some() {
+pub fn init(subscriber: impl Subscriber) -> Process<Message> {
+    init_with_backpressure(subscriber, Backpressure::default())
+}
+
+/// Initialize a subscriber to handle log events, with a policy for events
+/// arriving faster than they are handled.
+///
+/// See the [`backpressure`] module.
+pub fn init_with_backpressure(
+    subscriber: impl Subscriber,
+    backpressure: Backpressure,
+) -> Process<Message> {
+    if Process::<Message>::lookup(&LoggingProcessID).is_some() {
         panic!("logger already initialized");
     }
 
-    let process = spawn_subscriber(subscriber);
+    let published = Published {
+        interest: subscriber.interest(),
+        acknowledge: backpressure.acknowledge(),
+    };
+    let interest_process = spawn_interest(published.clone());
+    interest_process.register(&InterestProcessID);
+    let interest_id = Some(interest_process.id());
+    let publisher = Publisher {
+        process: interest_process,
+        published: published.clone(),
+    };
+    let process = spawn_subscriber_process(subscriber, backpressure, Some(publisher));
     process.register(&LoggingProcessID);
-    LOGGING_PROCESS.with_borrow_mut(|mut proc| *proc = LoggingProcess::Present(process.clone()));
+    LOGGING_PROCESS.with_borrow_mut(|mut proc| {
+        *proc = LoggingProcess::Present {
+            process: process.clone(),
+            published,
+            interest_id,
+            checked_at: Instant::now(),
+        }
+    });
     process
 }
 
-/// Spawn a subscriber process.
-pub fn spawn_subscriber(subscriber: impl Subscriber) -> Process<Event> {
-    spawn_link!(|subscriber, mailbox: Mailbox<Event>| {
+/// Spawns a process publishing the subscriber's [`Interest`] to emitting processes.
+///
+/// This is a separate process so that emitting processes don't have to wait
+/// behind the events queued for the subscriber.
+fn spawn_interest(published: Published) -> Process<InterestMessage> {
+    spawn_link!(|published, mailbox: Mailbox<InterestMessage>| {
+        let mut published = published;
+        let mut replaced = false;
         loop {
-            let event = mailbox.receive();
+            let message = if replaced {
+                // Emitting processes may still ask until their next check.
+                match mailbox.receive_timeout(INTEREST_REFRESH_INTERVAL) {
+                    MailboxResult::Message(message) => message,
+                    _ => break,
+                }
+            } else {
+                mailbox.receive()
+            };
+            match message {
+                InterestMessage::Get(proc, tag) => proc.tag_send(tag, published.clone()),
+                InterestMessage::Replace(new) => {
+                    published = new;
+                    replaced = true;
+                }
+                InterestMessage::Stop => break,
+            }
+        }
+    })
+}
+
+/// Asks an interest process for the published state of the subscriber,
+/// returning `None` if it doesn't reply.
+fn request_published(process: &Process<InterestMessage>) -> Option<Published> {
+    let tag = Tag::new();
+    process.send(InterestMessage::Get(Process::this(), tag));
+    // Safety: only the reply carrying `tag` is received, which is always a `Published`.
+    let mailbox: Mailbox<Published> = unsafe { Mailbox::new() };
+    match mailbox.tag_receive_timeout(&[tag], REQUEST_TIMEOUT) {
+        MailboxResult::Message(published) => Some(published),
+        _ => None,
+    }
+}
+
+/// Spawn a subscriber process.
+pub fn spawn_subscriber(subscriber: impl Subscriber) -> Process<Message> {
+    spawn_subscriber_with_backpressure(subscriber, Backpressure::default())
+}
+
+/// Spawn a subscriber process, with a policy for events arriving faster than
+/// they are handled.
+///
+/// With [`Overflow::Block`](backpressure::Overflow::Block), events need to be
+/// sent as [`Message::BlockingEvent`] to make the sender wait.
+pub fn spawn_subscriber_with_backpressure(
+    subscriber: impl Subscriber,
+    backpressure: Backpressure,
+) -> Process<Message> {
+    spawn_subscriber_process(subscriber, backpressure, None)
+}
+
+/// Spawns a subscriber process, which publishes its interest to the interest
+/// process if one is given.
+fn spawn_subscriber_process(
+    subscriber: impl Subscriber,
+    backpressure: Backpressure,
+    publisher: Option<Publisher>,
+) -> Process<Message> {
+    spawn_link!(
+        |subscriber, backpressure, publisher, mailbox: Mailbox<Message>| {
+            let mut subscriber = subscriber;
+            let mut publisher = publisher;
+            let mut queue = Queue::new(backpressure);
+            loop {
+                if queue.is_empty() {
+                    let message = match queue.summary_timeout() {
+                        Some(timeout) => match mailbox.receive_timeout(timeout) {
+                            MailboxResult::Message(message) => Some(message),
+                            _ => None,
+                        },
+                        None => Some(mailbox.receive()),
+                    };
+                    if let Some(message) = message {
+                        queue.push(message);
+                    }
+                }
+                // Move every waiting message into the queue, so that the
+                // policy applies before the mailbox grows.
+                while let MailboxResult::Message(message) = mailbox.receive_timeout(Duration::ZERO)
+                {
+                    queue.push(message);
+                }
+                if let Some(summary) = queue.summary(false) {
+                    handle_message(&mut subscriber, &mut publisher, Message::Event(summary));
+                }
+
+                match queue.pop() {
+                    Some(Message::Shutdown(proc, tag)) => {
+                        while let MailboxResult::Message(message) =
+                            mailbox.receive_timeout(Duration::ZERO)
+                        {
+                            queue.push(message);
+                        }
+                        while let Some(message) = queue.pop() {
+                            handle_message(&mut subscriber, &mut publisher, message);
+                        }
+                        if let Some(summary) = queue.summary(true) {
+                            handle_message(
+                                &mut subscriber,
+                                &mut publisher,
+                                Message::Event(summary),
+                            );
+                        }
+                        subscriber.flush();
+                        if let Some(publisher) = &publisher {
+                            publisher.stop();
+                        }
+                        proc.tag_send(tag, ());
+                        break;
+                    }
+                    Some(message @ Message::Flush(..)) => {
+                        if let Some(summary) = queue.summary(true) {
+                            handle_message(
+                                &mut subscriber,
+                                &mut publisher,
+                                Message::Event(summary),
+                            );
+                        }
+                        handle_message(&mut subscriber, &mut publisher, message);
+                    }
+                    Some(message) => handle_message(&mut subscriber, &mut publisher, message),
+                    None => {}
+                }
+            }
+        }
+    )
+}
+
+fn handle_message(
+    subscriber: &mut impl Subscriber,
+    publisher: &mut Option<Publisher>,
+    message: Message,
+) {
+    match message {
+        Message::Event(event) | Message::BlockingEvent(event, _, _) => {
             if subscriber.enabled(event.metadata()) {
                 subscriber.event(&event);
             }
         }
-    })
+        Message::Events(events) | Message::BlockingEvents(events, _, _) => {
+            for event in events {
+                if subscriber.enabled(event.metadata()) {
+                    subscriber.event(&event);
+                }
+            }
+        }
+        Message::NewSpan(span) => {
+            if subscriber.enabled(span.metadata()) {
+                subscriber.new_span(&span);
+            }
+        }
+        Message::Enter(id) => subscriber.enter(&id),
+        Message::Exit(id) => subscriber.exit(&id),
+        Message::Close(id) => subscriber.close(&id),
+        Message::SetFilter(filter, proc, tag) => {
+            subscriber.set_filter(filter);
+            if let Some(publisher) = publisher {
+                publisher.publish(subscriber.interest());
+            }
+            proc.tag_send(tag, ());
+        }
+        Message::Flush(proc, tag) | Message::Shutdown(proc, tag) => {
+            subscriber.flush();
+            proc.tag_send(tag, ());
+        }
+    }
 }
 
-/// An event to be logged by a subscriber, storing a message and metadata.
+/// Waits until the subscriber has handled every event sent by the current
+/// process, and flushed any buffered output.
+///
+/// This should be called before the application exits, so that no events are lost.
+pub fn flush() {
+    buffer::send_buffered();
+    if let Some(proc) = __lookup_logging_process() {
+        request(&proc, Message::Flush);
+    }
+}
+
+/// Replaces the filter of the subscriber at runtime.
+///
+/// Accepts a [`LevelFilter`] or an [`EnvFilter`]. Other processes pick up the
+/// change to the subscriber's [`Interest`] within a second, and use the
+/// previous [`Interest`] until then. Only the first event a process emits
+/// after noticing the change waits for the new [`Interest`].
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::filter::EnvFilter;
+///
+/// let filter: EnvFilter = "info,my_app::db=debug".parse().unwrap();
+/// lunatic_log::set_filter(filter);
+/// ```
+pub fn set_filter(filter: impl Into<EnvFilter>) {
+    if let Some(proc) = __lookup_logging_process() {
+        let filter = filter.into();
+        request(&proc, |proc, tag| Message::SetFilter(filter, proc, tag));
+        LOGGING_PROCESS.with_borrow_mut(|mut proc| *proc = LoggingProcess::NotLookedUp);
+    }
+}
+
+/// Stops the subscriber, after it has handled every message in its mailbox and
+/// flushed any buffered output.
+///
+/// Events emitted after the subscriber has stopped are discarded, and a new
+/// subscriber can be initialized with [`init`].
+pub fn shutdown() {
+    buffer::send_buffered();
+    if let Some(proc) = __lookup_logging_process() {
+        request(&proc, Message::Shutdown);
+        LOGGING_PROCESS.with_borrow_mut(|mut proc| *proc = LoggingProcess::NotPresent);
+    }
+}
+
+/// Sends a message to a subscriber process and waits for its reply.
+///
+/// Gives up after [`REQUEST_TIMEOUT`], in case the subscriber stopped, and
+/// returns immediately when called from the subscriber process itself, which
+/// can't reply while waiting.
+pub(crate) fn request(proc: &Process<Message>, message: impl FnOnce(Process<()>, Tag) -> Message) {
+    if proc.id() == Process::<()>::this().id() {
+        return;
+    }
+    let tag = Tag::new();
+    proc.send(message(Process::this(), tag));
+    // Safety: only the reply carrying `tag` is received, which is always a `()`.
+    let mailbox: Mailbox<()> = unsafe { Mailbox::new() };
+    let _ = mailbox.tag_receive_timeout(&[tag], REQUEST_TIMEOUT);
+}
+
+/// Removes a process name from the registry.
+fn unregister(name: &impl ProcessName) {
+    let name = name.process_name();
+    // Safety: the pointer and length describe a valid string for the duration of the call.
+    unsafe { lunatic::host::api::registry::remove(name.as_ptr(), name.len()) };
+}
+
+/// A message handled by a subscriber process.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Message {
+    /// A log [`Event`].
+    Event(Event),
+    /// A log [`Event`], acknowledged by replying to the process with the tag
+    /// once the subscriber has room for it.
+    BlockingEvent(Event, Process<()>, Tag),
+    /// A batch of log [`Event`]s, sent by a process with [buffering](buffer).
+    Events(Vec<Event>),
+    /// A batch of log [`Event`]s, acknowledged by replying to the process
+    /// with the tag once the subscriber has room for them.
+    BlockingEvents(Vec<Event>, Process<()>, Tag),
+    /// A new span was created.
+    NewSpan(SpanContext),
+    /// A span was entered.
+    Enter(SpanId),
+    /// A span was exited.
+    Exit(SpanId),
+    /// A span was closed.
+    Close(SpanId),
+    /// Replace the subscriber's filter, then reply to the process with the tag.
+    SetFilter(EnvFilter, Process<()>, Tag),
+    /// Flush buffered output, then reply to the process with the tag.
+    Flush(Process<()>, Tag),
+    /// Handle all queued messages and flush, then reply to the process with the
+    /// tag and stop the subscriber.
+    Shutdown(Process<()>, Tag),
+}
+
+/// An event to be logged by a subscriber, storing a message, field values and metadata.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Event {
     message: String,
     metadata: Metadata,
+    values: Vec<Value>,
+    spans: Vec<SpanContext>,
+    context: Context,
+    timestamp: Option<SystemTime>,
 }
 
 impl Event {
-    /// Creates a new event given a message and metadata.
-    pub const fn new(message: String, metadata: Metadata) -> Self {
-        Event { metadata, message }
+    /// Creates a new event given a message, metadata and field values.
+    ///
+    /// The values must be in the same order as the field names in [`Metadata::fields`].
+    pub const fn new(message: String, metadata: Metadata, values: Vec<Value>) -> Self {
+        Event {
+            metadata,
+            message,
+            values,
+            spans: Vec::new(),
+            context: Context::new(),
+            timestamp: None,
+        }
+    }
+
+    /// Returns the time this `Event` was emitted, or the current time if it
+    /// wasn't sent by an emitting process.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp.unwrap_or_else(SystemTime::now)
     }
 
     /// Returns the message string to be logged.
@@ -94,29 +513,174 @@ impl Event {
     pub fn metadata(&self) -> &Metadata {
         &self.metadata
     }
+
+    /// Returns the field values of this `Event`, in the order of [`Metadata::fields`].
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Returns an iterator over the field names and values of this `Event`.
+    pub fn fields(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.metadata.fields().iter().zip(&self.values)
+    }
+
+    /// Returns the value of the field with the given name, if it exists.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.fields()
+            .find(|(field, _)| field.as_str() == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Records an additional field on this `Event`, replacing the value if a
+    /// field with the same name exists.
+    ///
+    /// This is used by [layers](subscriber::layer::Layer) enriching events.
+    pub fn record(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        let name = name.into();
+        match self
+            .metadata
+            .fields()
+            .iter()
+            .position(|field| *field == name)
+        {
+            Some(index) => self.values[index] = value.into(),
+            None => {
+                self.metadata.push_field(name);
+                self.values.push(value.into());
+            }
+        }
+    }
+
+    /// Returns the contexts of the spans the emitting process had entered when
+    /// this `Event` was sent, from outermost to innermost.
+    pub fn spans(&self) -> &[SpanContext] {
+        &self.spans
+    }
+
+    /// Returns the [context](context) of the emitting process when this `Event` was sent.
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
 }
 
 // This is an internal function, and it's API is subject to change at any time.
 #[doc(hidden)]
-pub fn __lookup_logging_process() -> Option<Process<Event>> {
-    LOGGING_PROCESS.with(|proc| {
-        let proc_ref = proc.borrow();
-        match &*proc_ref {
-            LoggingProcess::NotLookedUp => {
-                std::mem::drop(proc_ref);
-                match Process::<Event>::lookup(&LoggingProcessID) {
-                    Some(process) => {
-                        *proc.borrow_mut() = LoggingProcess::Present(process.clone());
-                        Some(process)
-                    }
-                    None => {
-                        *proc.borrow_mut() = LoggingProcess::NotPresent;
-                        None
-                    }
-                }
-            }
-            LoggingProcess::NotPresent => None,
-            LoggingProcess::Present(process) => Some(process.clone()),
+pub fn __send_event(proc: &Process<Message>, mut event: Event) {
+    event.timestamp = Some(SystemTime::now());
+    event.spans = span::current_spans();
+    event.context = CONTEXT.with(|context| context.borrow().clone());
+    let event = match buffer::push(proc, event) {
+        Some(event) => event,
+        None => return,
+    };
+    if acknowledge(proc) {
+        request(proc, |proc, tag| Message::BlockingEvent(event, proc, tag));
+    } else {
+        proc.send(Message::Event(event));
+    }
+}
+
+/// Sends a batch of events to a subscriber process.
+pub(crate) fn send_events(proc: &Process<Message>, events: Vec<Event>) {
+    if acknowledge(proc) {
+        request(proc, |proc, tag| Message::BlockingEvents(events, proc, tag));
+    } else {
+        proc.send(Message::Events(events));
+    }
+}
+
+/// Returns `true` if events sent to the subscriber process need to be acknowledged.
+fn acknowledge(proc: &Process<Message>) -> bool {
+    let acknowledge = LOGGING_PROCESS.with(|logging_process| match &*logging_process.borrow() {
+        LoggingProcess::Present { published, .. } => published.acknowledge,
+        _ => false,
+    });
+    // The subscriber process can't wait for itself to acknowledge an event.
+    acknowledge && proc.id() != Process::<()>::this().id()
+}
+
+// This is an internal function, and it's API is subject to change at any time.
+#[doc(hidden)]
+pub fn __lookup_logging_process() -> Option<Process<Message>> {
+    LOGGING_PROCESS.with(|proc| match &*lookup(proc) {
+        LoggingProcess::Present { process, .. } => Some(process.clone()),
+        _ => None,
+    })
+}
+
+// This is an internal function, and it's API is subject to change at any time.
+#[doc(hidden)]
+pub fn __lookup_enabled_logging_process(
+    level: Level,
+    target: &str,
+    module_path: &str,
+) -> Option<Process<Message>> {
+    LOGGING_PROCESS.with(|proc| match &*lookup(proc) {
+        LoggingProcess::Present {
+            process,
+            published: Published { interest, .. },
+            ..
+        } if interest.enabled(level, target)
+            || (module_path != target && interest.enabled(level, module_path)) =>
+        {
+            Some(process.clone())
         }
+        _ => None,
     })
 }
+
+fn lookup(proc: &RefCell<LoggingProcess>) -> std::cell::Ref<'_, LoggingProcess> {
+    let outdated = match &*proc.borrow() {
+        LoggingProcess::NotLookedUp => true,
+        LoggingProcess::NotPresent => false,
+        LoggingProcess::Present { checked_at, .. } => {
+            checked_at.elapsed() >= INTEREST_REFRESH_INTERVAL
+        }
+    };
+    if outdated {
+        let previous = std::mem::replace(&mut *proc.borrow_mut(), LoggingProcess::NotPresent);
+        *proc.borrow_mut() = refresh(previous);
+    }
+    proc.borrow()
+}
+
+/// Looks up the subscriber in the registry, only asking for the published
+/// state if the subscriber or its interest process changed.
+fn refresh(previous: LoggingProcess) -> LoggingProcess {
+    let process = match Process::<Message>::lookup(&LoggingProcessID) {
+        Some(process) => process,
+        None => return LoggingProcess::NotPresent,
+    };
+    let interest_process = Process::<InterestMessage>::lookup(&InterestProcessID);
+    let interest_id = interest_process.as_ref().map(Process::id);
+    if let LoggingProcess::Present {
+        process: previous_process,
+        published,
+        interest_id: previous_interest_id,
+        ..
+    } = previous
+    {
+        if previous_process.id() == process.id()
+            && interest_id.is_some()
+            && previous_interest_id == interest_id
+        {
+            return LoggingProcess::Present {
+                process,
+                published,
+                interest_id,
+                checked_at: Instant::now(),
+            };
+        }
+    }
+    let published = interest_process.as_ref().and_then(request_published);
+    LoggingProcess::Present {
+        process,
+        // Asked again at the next check if the interest process didn't reply.
+        interest_id: published.as_ref().and(interest_id),
+        published: published.unwrap_or(Published {
+            interest: Interest::always(),
+            acknowledge: false,
+        }),
+        checked_at: Instant::now(),
+    }
+}
@@ -0,0 +1,144 @@
+//! Bridge from the [`log`](::log) crate.
+//!
+//! Records logged with `log::info!` and the other macros of the `log` crate,
+//! for example by dependencies, are converted into [`Event`]s and sent to the
+//! subscriber, alongside the events of `lunatic-log`. Their key-values are
+//! recorded as fields.
+//!
+//! The `log` crate stores its logger in a static, and every lunatic process
+//! has its own memory, so the bridge needs to be installed with [`install`]
+//! in each process that logs through the `log` crate.
+//!
+//! This module requires the `log` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use lunatic::spawn_link;
+//! use lunatic_log::{subscriber::fmt::FmtSubscriber, LevelFilter};
+//!
+//! # fn main() {
+//! lunatic_log::init(FmtSubscriber::new(LevelFilter::Info));
+//! lunatic_log::log::install().unwrap();
+//! log::info!("Hello from the log crate");
+//!
+//! spawn_link!(|_mailbox: Mailbox<()>| {
+//!     lunatic_log::log::install().unwrap();
+//!     log::info!("Hello from another process");
+//! });
+//! # }
+//! ```
+
+use ::log::{
+    kv::{self, VisitSource},
+    Log, Record, SetLoggerError,
+};
+
+use crate::{Event, Level, LevelFilter, Metadata, Value, STATIC_MAX_LEVEL};
+
+static LOGGER: Logger = Logger;
+
+/// Installs the bridge as the logger of the `log` crate in the current process.
+///
+/// Returns an error if a logger was already set in this process.
+pub fn install() -> Result<(), SetLoggerError> {
+    ::log::set_logger(&LOGGER)?;
+    ::log::set_max_level(level_filter_to_log(STATIC_MAX_LEVEL));
+    Ok(())
+}
+
+/// A [`Log`] implementation sending records to the subscriber process.
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &::log::Metadata<'_>) -> bool {
+        let level = level_from_log(metadata.level());
+        level <= STATIC_MAX_LEVEL
+            && crate::__lookup_enabled_logging_process(level, metadata.target(), metadata.target())
+                .is_some()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let level = level_from_log(record.level());
+        if level > STATIC_MAX_LEVEL {
+            return;
+        }
+        let module_path = record.module_path().unwrap_or(record.target());
+        let proc =
+            match crate::__lookup_enabled_logging_process(level, record.target(), module_path) {
+                Some(proc) => proc,
+                None => return,
+            };
+
+        let mut fields = Fields::default();
+        let _ = record.key_values().visit(&mut fields);
+        let name = match (record.file(), record.line()) {
+            (Some(file), Some(line)) => format!("event {file}:{line}"),
+            _ => format!("event {}", record.target()),
+        };
+        let metadata = Metadata::new(
+            name,
+            record.target().to_string(),
+            level,
+            fields.names,
+            record.module_path().map(str::to_string),
+            record.file().map(str::to_string),
+            record.line(),
+        );
+        let event = Event::new(record.args().to_string(), metadata, fields.values);
+        crate::__send_event(&proc, event);
+    }
+
+    fn flush(&self) {
+        crate::flush();
+    }
+}
+
+/// Collects the key-values of a record.
+#[derive(Default)]
+struct Fields {
+    names: Vec<String>,
+    values: Vec<Value>,
+}
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(n) = value.to_i64() {
+            Value::I64(n)
+        } else if let Some(n) = value.to_u64() {
+            Value::U64(n)
+        } else if let Some(n) = value.to_f64() {
+            Value::F64(n)
+        } else if let Some(b) = value.to_bool() {
+            Value::Bool(b)
+        } else if let Some(s) = value.to_borrowed_str() {
+            Value::Str(s.to_string())
+        } else {
+            Value::display(&value)
+        };
+        self.names.push(key.as_str().to_string());
+        self.values.push(value);
+        Ok(())
+    }
+}
+
+fn level_from_log(level: ::log::Level) -> Level {
+    match level {
+        ::log::Level::Error => Level::Error,
+        ::log::Level::Warn => Level::Warn,
+        ::log::Level::Info => Level::Info,
+        ::log::Level::Debug => Level::Debug,
+        ::log::Level::Trace => Level::Trace,
+    }
+}
+
+fn level_filter_to_log(level_filter: LevelFilter) -> ::log::LevelFilter {
+    match level_filter {
+        LevelFilter::Off => ::log::LevelFilter::Off,
+        LevelFilter::Error => ::log::LevelFilter::Error,
+        LevelFilter::Warn => ::log::LevelFilter::Warn,
+        LevelFilter::Info => ::log::LevelFilter::Info,
+        LevelFilter::Debug => ::log::LevelFilter::Debug,
+        LevelFilter::Trace => ::log::LevelFilter::Trace,
+    }
+}
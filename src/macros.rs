@@ -1,34 +1,89 @@
 /// Logs a message with a specified level.
 ///
+/// Structured fields can be recorded before the message as a list of
+/// `key = value` pairs terminated by a `;`. Values are converted into a
+/// [`Value`](crate::Value) with [`From`], or captured with their `Debug`
+/// or `Display` implementation when prefixed with `?` or `%`.
+///
 /// You should use [`error`], [`warn`], [`info`], [`debug`], [`trace`] macros instead.
 #[macro_export]
 macro_rules! log {
-    // log!(target: "my_target", Level::Info; "a {} event", "log");
-    (target: $target:expr, $lvl:expr, $($arg:tt)+) => ({
-        if let Some(proc) = $crate::__lookup_logging_process() {
-            let metadata = $crate::Metadata::new(
-                concat!(
-                    "event ",
-                    file!(),
-                    ":",
-                    line!()
-                ).to_string(),
-                $target.into(),
-                $lvl,
-                Some(module_path!().to_string()),
-                Some(file!().to_string()),
-                Some(line!()),
-            );
-            let message = format!($($arg)+);
-            let event = $crate::Event::new(message, metadata);
-            proc.send(event)
-        }
-    });
+    // log!(target: "my_target", Level::Info, key1 = 42, key2 = true; "a {} event", "log");
+    (target: $target:expr, $lvl:expr, $key:ident = $($arg:tt)+) => (
+        $crate::__log_fields!([$target, $lvl] [] $key = $($arg)+)
+    );
+
+    // log!(target: "my_target", Level::Info, "a {} event", "log");
+    (target: $target:expr, $lvl:expr, $($arg:tt)+) => (
+        $crate::__log_fields!([$target, $lvl] []; $($arg)+)
+    );
 
     // log!(Level::Info, "a log event")
     ($lvl:expr, $($arg:tt)+) => ($crate::log!(target: module_path!(), $lvl, $($arg)+));
 }
 
+// Collects the `key = value` fields of a log event and emits it.
+//
+// This is an internal macro, and it's API is subject to change at any time.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_fields {
+    // All fields collected, emit the event.
+    ([$target:expr, $lvl:expr] [$(($key:ident, $value:expr))*]; $($arg:tt)+) => ({
+        let lvl = $lvl;
+        let target = $target;
+        if lvl <= $crate::STATIC_MAX_LEVEL {
+            if let Some(proc) = $crate::__lookup_enabled_logging_process(
+                lvl,
+                ::core::convert::AsRef::<str>::as_ref(&target),
+                module_path!(),
+            ) {
+                let metadata = $crate::Metadata::new(
+                    concat!(
+                        "event ",
+                        file!(),
+                        ":",
+                        line!()
+                    ).to_string(),
+                    target.into(),
+                    lvl,
+                    vec![$(stringify!($key).to_string()),*],
+                    Some(module_path!().to_string()),
+                    Some(file!().to_string()),
+                    Some(line!()),
+                );
+                let message = format!($($arg)+);
+                let event = $crate::Event::new(message, metadata, vec![$($value),*]);
+                $crate::__send_event(&proc, event)
+            }
+        }
+    });
+
+    // key = ?value
+    ($head:tt [$($fields:tt)*] $key:ident = ?$value:expr, $($rest:tt)+) => (
+        $crate::__log_fields!($head [$($fields)* ($key, $crate::Value::debug(&$value))] $($rest)+)
+    );
+    ($head:tt [$($fields:tt)*] $key:ident = ?$value:expr; $($rest:tt)+) => (
+        $crate::__log_fields!($head [$($fields)* ($key, $crate::Value::debug(&$value))]; $($rest)+)
+    );
+
+    // key = %value
+    ($head:tt [$($fields:tt)*] $key:ident = %$value:expr, $($rest:tt)+) => (
+        $crate::__log_fields!($head [$($fields)* ($key, $crate::Value::display(&$value))] $($rest)+)
+    );
+    ($head:tt [$($fields:tt)*] $key:ident = %$value:expr; $($rest:tt)+) => (
+        $crate::__log_fields!($head [$($fields)* ($key, $crate::Value::display(&$value))]; $($rest)+)
+    );
+
+    // key = value
+    ($head:tt [$($fields:tt)*] $key:ident = $value:expr, $($rest:tt)+) => (
+        $crate::__log_fields!($head [$($fields)* ($key, $crate::Value::from($value))] $($rest)+)
+    );
+    ($head:tt [$($fields:tt)*] $key:ident = $value:expr; $($rest:tt)+) => (
+        $crate::__log_fields!($head [$($fields)* ($key, $crate::Value::from($value))]; $($rest)+)
+    );
+}
+
 /// Logs a message at the error level.
 ///
 /// # Examples
@@ -91,6 +146,7 @@ macro_rules! warn {
 /// info!("Connected to port {} at {} Mb/s", conn_info.port, conn_info.speed);
 /// info!(target: "connection_events", "Successfull connection, port: {}, speed: {}",
 ///       conn_info.port, conn_info.speed);
+/// info!(port = conn_info.port, speed = conn_info.speed; "Successfull connection");
 /// # }
 /// ```
 #[macro_export(local_inner_macros)]
@@ -154,3 +210,280 @@ macro_rules! trace {
     // trace!("a {} event", "log")
     ($($arg:tt)+) => (log!($crate::Level::Trace, $($arg)+))
 }
+
+/// Constructs a new span with a specified level.
+///
+/// The span's parent defaults to the innermost span entered by the current
+/// process, and can be set explicitly with `parent:`, for example to a
+/// [`SpanId`](crate::SpanId) received from another process. Fields are
+/// recorded after the name with the same `key = value` syntax as [`log`].
+///
+/// You should use [`error_span`], [`warn_span`], [`info_span`], [`debug_span`], [`trace_span`] macros instead.
+///
+/// # Examples
+///
+/// ```
+/// use lunatic_log::{span, Level};
+///
+/// # fn main() {
+/// let span = span!(Level::Info, "request", request_id = 42, path = "/");
+/// let _guard = span.enter();
+/// # }
+/// ```
+#[macro_export]
+macro_rules! span {
+    // span!(target: "my_target", parent: id, Level::Info, "my_span", key1 = 42)
+    (target: $target:expr, parent: $parent:expr, $lvl:expr, $name:expr $(, $($fields:tt)*)?) => (
+        $crate::__span_fields!(
+            [$target, ::core::convert::Into::<Option<$crate::SpanId>>::into($parent), $lvl, $name]
+            []
+            $($($fields)*)?
+        )
+    );
+
+    // span!(target: "my_target", Level::Info, "my_span", key1 = 42)
+    (target: $target:expr, $lvl:expr, $name:expr $(, $($fields:tt)*)?) => (
+        $crate::__span_fields!(
+            [$target, $crate::current_span(), $lvl, $name]
+            []
+            $($($fields)*)?
+        )
+    );
+
+    // span!(parent: id, Level::Info, "my_span", key1 = 42)
+    (parent: $parent:expr, $lvl:expr, $name:expr $(, $($fields:tt)*)?) => (
+        $crate::span!(target: module_path!(), parent: $parent, $lvl, $name $(, $($fields)*)?)
+    );
+
+    // span!(Level::Info, "my_span", key1 = 42)
+    ($lvl:expr, $name:expr $(, $($fields:tt)*)?) => (
+        $crate::span!(target: module_path!(), $lvl, $name $(, $($fields)*)?)
+    );
+}
+
+// Collects the `key = value` fields of a span and creates it.
+//
+// This is an internal macro, and it's API is subject to change at any time.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __span_fields {
+    // All fields collected, create the span.
+    ([$target:expr, $parent:expr, $lvl:expr, $name:expr] [$(($key:ident, $value:expr))*]) => ({
+        let lvl = $lvl;
+        let target = $target;
+        if lvl <= $crate::STATIC_MAX_LEVEL
+            && $crate::__lookup_enabled_logging_process(
+                lvl,
+                ::core::convert::AsRef::<str>::as_ref(&target),
+                module_path!(),
+            )
+            .is_some()
+        {
+            let metadata = $crate::Metadata::new(
+                ::std::string::ToString::to_string($name),
+                target.into(),
+                lvl,
+                vec![$(stringify!($key).to_string()),*],
+                Some(module_path!().to_string()),
+                Some(file!().to_string()),
+                Some(line!()),
+            );
+            $crate::Span::new(metadata, vec![$($value),*], $parent)
+        } else {
+            $crate::Span::none()
+        }
+    });
+
+    // key = ?value
+    ($head:tt [$($fields:tt)*] $key:ident = ?$value:expr $(, $($rest:tt)*)?) => (
+        $crate::__span_fields!($head [$($fields)* ($key, $crate::Value::debug(&$value))] $($($rest)*)?)
+    );
+
+    // key = %value
+    ($head:tt [$($fields:tt)*] $key:ident = %$value:expr $(, $($rest:tt)*)?) => (
+        $crate::__span_fields!($head [$($fields)* ($key, $crate::Value::display(&$value))] $($($rest)*)?)
+    );
+
+    // key = value
+    ($head:tt [$($fields:tt)*] $key:ident = $value:expr $(, $($rest:tt)*)?) => (
+        $crate::__span_fields!($head [$($fields)* ($key, $crate::Value::from($value))] $($($rest)*)?)
+    );
+}
+
+/// Constructs a span at the error level.
+///
+/// # Examples
+///
+/// ```
+/// use lunatic_log::error_span;
+///
+/// # fn main() {
+/// let span = error_span!("shutdown", reason = "out of memory");
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! error_span {
+    // error_span!(target: "my_target", parent: id, "my_span", key1 = 42)
+    (target: $target:expr, parent: $parent:expr, $($arg:tt)+) => (
+        span!(target: $target, parent: $parent, $crate::Level::Error, $($arg)+)
+    );
+
+    // error_span!(target: "my_target", "my_span", key1 = 42)
+    (target: $target:expr, $($arg:tt)+) => (span!(target: $target, $crate::Level::Error, $($arg)+));
+
+    // error_span!(parent: id, "my_span", key1 = 42)
+    (parent: $parent:expr, $($arg:tt)+) => (span!(parent: $parent, $crate::Level::Error, $($arg)+));
+
+    // error_span!("my_span", key1 = 42)
+    ($($arg:tt)+) => (span!($crate::Level::Error, $($arg)+))
+}
+
+/// Constructs a span at the warn level.
+///
+/// # Examples
+///
+/// ```
+/// use lunatic_log::warn_span;
+///
+/// # fn main() {
+/// let span = warn_span!(target: "input_events", "validation", field = "email");
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! warn_span {
+    // warn_span!(target: "my_target", parent: id, "my_span", key1 = 42)
+    (target: $target:expr, parent: $parent:expr, $($arg:tt)+) => (
+        span!(target: $target, parent: $parent, $crate::Level::Warn, $($arg)+)
+    );
+
+    // warn_span!(target: "my_target", "my_span", key1 = 42)
+    (target: $target:expr, $($arg:tt)+) => (span!(target: $target, $crate::Level::Warn, $($arg)+));
+
+    // warn_span!(parent: id, "my_span", key1 = 42)
+    (parent: $parent:expr, $($arg:tt)+) => (span!(parent: $parent, $crate::Level::Warn, $($arg)+));
+
+    // warn_span!("my_span", key1 = 42)
+    ($($arg:tt)+) => (span!($crate::Level::Warn, $($arg)+))
+}
+
+/// Constructs a span at the info level.
+///
+/// # Examples
+///
+/// ```
+/// use lunatic_log::info_span;
+///
+/// # fn main() {
+/// let span = info_span!("request", request_id = 42, user = "alice");
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! info_span {
+    // info_span!(target: "my_target", parent: id, "my_span", key1 = 42)
+    (target: $target:expr, parent: $parent:expr, $($arg:tt)+) => (
+        span!(target: $target, parent: $parent, $crate::Level::Info, $($arg)+)
+    );
+
+    // info_span!(target: "my_target", "my_span", key1 = 42)
+    (target: $target:expr, $($arg:tt)+) => (span!(target: $target, $crate::Level::Info, $($arg)+));
+
+    // info_span!(parent: id, "my_span", key1 = 42)
+    (parent: $parent:expr, $($arg:tt)+) => (span!(parent: $parent, $crate::Level::Info, $($arg)+));
+
+    // info_span!("my_span", key1 = 42)
+    ($($arg:tt)+) => (span!($crate::Level::Info, $($arg)+))
+}
+
+/// Constructs a span at the debug level.
+///
+/// # Examples
+///
+/// ```
+/// use lunatic_log::debug_span;
+///
+/// # fn main() {
+/// let span = debug_span!("query", sql = "SELECT 1");
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! debug_span {
+    // debug_span!(target: "my_target", parent: id, "my_span", key1 = 42)
+    (target: $target:expr, parent: $parent:expr, $($arg:tt)+) => (
+        span!(target: $target, parent: $parent, $crate::Level::Debug, $($arg)+)
+    );
+
+    // debug_span!(target: "my_target", "my_span", key1 = 42)
+    (target: $target:expr, $($arg:tt)+) => (span!(target: $target, $crate::Level::Debug, $($arg)+));
+
+    // debug_span!(parent: id, "my_span", key1 = 42)
+    (parent: $parent:expr, $($arg:tt)+) => (span!(parent: $parent, $crate::Level::Debug, $($arg)+));
+
+    // debug_span!("my_span", key1 = 42)
+    ($($arg:tt)+) => (span!($crate::Level::Debug, $($arg)+))
+}
+
+/// Constructs a span at the trace level.
+///
+/// # Examples
+///
+/// ```
+/// use lunatic_log::trace_span;
+///
+/// # fn main() {
+/// let span = trace_span!("poll", attempt = 3);
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! trace_span {
+    // trace_span!(target: "my_target", parent: id, "my_span", key1 = 42)
+    (target: $target:expr, parent: $parent:expr, $($arg:tt)+) => (
+        span!(target: $target, parent: $parent, $crate::Level::Trace, $($arg)+)
+    );
+
+    // trace_span!(target: "my_target", "my_span", key1 = 42)
+    (target: $target:expr, $($arg:tt)+) => (span!(target: $target, $crate::Level::Trace, $($arg)+));
+
+    // trace_span!(parent: id, "my_span", key1 = 42)
+    (parent: $parent:expr, $($arg:tt)+) => (span!(parent: $parent, $crate::Level::Trace, $($arg)+));
+
+    // trace_span!("my_span", key1 = 42)
+    ($($arg:tt)+) => (span!($crate::Level::Trace, $($arg)+))
+}
+
+/// Spawns a linked process that inherits the logging [context](crate::context)
+/// of the current process.
+///
+/// This accepts the same closure syntax as [`lunatic::spawn_link`].
+///
+/// # Examples
+///
+/// ```
+/// use lunatic_log::{context, info, spawn_link_with_context};
+///
+/// # fn main() {
+/// context::insert("job", "resize");
+///
+/// let size = 512;
+/// spawn_link_with_context!(|size, _mailbox: Mailbox<()>| {
+///     info!("Resizing to {}", size);
+/// });
+/// # }
+/// ```
+#[macro_export]
+macro_rules! spawn_link_with_context {
+    // All captures collected, spawn the process.
+    (@captures [$($capture:ident)*] $mailbox:ident : Mailbox<$ty:ty> | { $($body:tt)* }) => ({
+        let __context = $crate::context::current();
+        $crate::__lunatic::spawn_link!(|__context, $($capture,)* $mailbox: Mailbox<$ty>| {
+            $crate::context::set(__context);
+            $($body)*
+        })
+    });
+
+    (@captures [$($captures:ident)*] $capture:ident, $($rest:tt)*) => (
+        $crate::spawn_link_with_context!(@captures [$($captures)* $capture] $($rest)*)
+    );
+
+    // spawn_link_with_context!(|capture, mailbox: Mailbox<T>| { ... })
+    (|$($rest:tt)*) => ($crate::spawn_link_with_context!(@captures [] $($rest)*));
+}
@@ -33,6 +33,9 @@ pub struct Metadata {
     /// The level of verbosity of the described event.
     level: Level,
 
+    /// The names of the fields recorded by the described event.
+    fields: Vec<String>,
+
     /// The name of the Rust module where the event occurred, or `None` if this
     /// could not be determined.
     module_path: Option<String>,
@@ -53,6 +56,7 @@ impl Metadata {
         name: String,
         target: String,
         level: Level,
+        fields: Vec<String>,
         module_path: Option<String>,
         file: Option<String>,
         line: Option<u32>,
@@ -61,6 +65,7 @@ impl Metadata {
             name,
             target,
             level,
+            fields,
             module_path,
             file,
             line,
@@ -77,6 +82,18 @@ impl Metadata {
         &self.name
     }
 
+    /// Returns the names of the fields on the described span or event.
+    ///
+    /// The names are in the same order as the values returned by
+    /// [`Event::values`](super::Event::values).
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub(crate) fn push_field(&mut self, name: String) {
+        self.fields.push(name);
+    }
+
     /// Returns a string describing the part of the system where the span or
     /// event that this metadata describes occurred.
     ///
@@ -0,0 +1,96 @@
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    panic::{self, Location},
+};
+
+use lunatic::Process;
+
+use crate::{Event, Level, Message, Metadata, Value};
+
+/// The target of events logged by the panic hook.
+const PANIC_TARGET: &str = "panic";
+
+/// Installs a panic hook that logs panics of the current process through the
+/// subscriber.
+///
+/// A panic is logged as an [`Level::Error`] event with the panic message, and
+/// the file and line where the panic occurred. If the `RUST_BACKTRACE`
+/// environment variable enables backtraces, the backtrace is recorded in the
+/// `backtrace` field. The hook waits until the subscriber has handled the
+/// event before unwinding continues, so that it is not lost when the process
+/// dies.
+///
+/// If no subscriber is initialized, or it is not interested in errors with
+/// the `panic` target, the previous hook is called instead, which prints to
+/// stderr by default.
+///
+/// The hook is set for the current process only, so this needs to be called
+/// in every process whose panics should be logged.
+///
+/// # Example
+///
+/// ```should_panic
+/// use lunatic_log::{subscriber::fmt::FmtSubscriber, LevelFilter};
+///
+/// lunatic_log::init(FmtSubscriber::new(LevelFilter::Info));
+/// lunatic_log::install_panic_hook();
+///
+/// panic!("Logged by the subscriber");
+/// ```
+pub fn install_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !log_panic(info.payload(), info.location()) {
+            previous(info);
+        }
+    }));
+}
+
+/// Sends a panic to the subscriber and waits for it to be handled, returning
+/// `false` if the subscriber is not interested in it.
+fn log_panic(payload: &(dyn Any + Send), location: Option<&Location<'_>>) -> bool {
+    if Level::Error > crate::STATIC_MAX_LEVEL {
+        return false;
+    }
+    let proc =
+        match crate::__lookup_enabled_logging_process(Level::Error, PANIC_TARGET, PANIC_TARGET) {
+            Some(proc) => proc,
+            None => return false,
+        };
+    // The subscriber process can't wait for itself to handle the event.
+    if proc.id() == Process::<()>::this().id() {
+        return false;
+    }
+
+    let payload = match payload.downcast_ref::<&str>() {
+        Some(s) => *s,
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => s.as_str(),
+            None => "Box<dyn Any>",
+        },
+    };
+    let message = match location {
+        Some(location) => format!("panicked at {location}: {payload}"),
+        None => format!("panicked: {payload}"),
+    };
+    let mut fields = Vec::new();
+    let mut values = Vec::new();
+    let backtrace = Backtrace::capture();
+    if backtrace.status() == BacktraceStatus::Captured {
+        fields.push("backtrace".to_string());
+        values.push(Value::display(&backtrace));
+    }
+    let metadata = Metadata::new(
+        "panic".to_string(),
+        PANIC_TARGET.to_string(),
+        Level::Error,
+        fields,
+        None,
+        location.map(|location| location.file().to_string()),
+        location.map(Location::line),
+    );
+    crate::__send_event(&proc, Event::new(message, metadata, values));
+    crate::request(&proc, Message::Flush);
+    true
+}
@@ -0,0 +1,296 @@
+use std::cell::{Cell, RefCell};
+
+use lunatic::{process_local, Process};
+use serde::{Deserialize, Serialize};
+
+use crate::{Level, Message, Metadata, Value, STATIC_MAX_LEVEL};
+
+process_local! {
+    static SPAN_STACK: RefCell<Vec<SpanContext>> = RefCell::new(Vec::new());
+    static NEXT_SPAN_ID: Cell<u64> = Cell::new(1);
+}
+
+/// Identifies a span and the trace it belongs to.
+///
+/// Span ids combine the id of the process that created the span, in the upper
+/// 64 bits, with a per-process counter, in the lower 64 bits, so they can be
+/// sent to other processes and used as the parent of spans created there.
+///
+/// A span id also carries the id of the trace, which is the id of the root
+/// span: spans inherit it from their parent, even when the parent was created
+/// in another process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SpanId {
+    id: u128,
+    trace: u128,
+}
+
+impl SpanId {
+    /// Creates the id of a root span from a `u128`.
+    pub const fn from_u128(id: u128) -> Self {
+        SpanId { id, trace: id }
+    }
+
+    /// Returns the span id as a `u128`.
+    pub const fn into_u128(self) -> u128 {
+        self.id
+    }
+
+    /// Returns the id of the trace, which is the id of the root span.
+    pub const fn trace_id(self) -> u128 {
+        self.trace
+    }
+
+    fn next(parent: Option<SpanId>) -> Self {
+        let counter = NEXT_SPAN_ID.with(|next| {
+            let id = next.get();
+            next.set(id.checked_add(1).expect("span ids exhausted"));
+            id
+        });
+        let process_id = Process::<()>::this().id();
+        let id = ((process_id as u128) << 64) | counter as u128;
+        SpanId {
+            id,
+            trace: parent.map_or(id, |parent| parent.trace),
+        }
+    }
+}
+
+/// Describes a span, storing its id, parent, metadata and field values.
+///
+/// A `SpanContext` is sent to the subscriber when a span is created, and every
+/// [Event](super::Event) carries the contexts of the spans entered when it was
+/// emitted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpanContext {
+    id: SpanId,
+    parent: Option<SpanId>,
+    metadata: Metadata,
+    values: Vec<Value>,
+}
+
+impl SpanContext {
+    /// Returns the id of the span.
+    pub fn id(&self) -> SpanId {
+        self.id
+    }
+
+    /// Returns the id of the parent span, which may live in another process.
+    pub fn parent(&self) -> Option<SpanId> {
+        self.parent
+    }
+
+    /// Returns the id of the trace, which is the id of the root span.
+    pub fn trace_id(&self) -> u128 {
+        self.id.trace
+    }
+
+    /// Returns [metadata] describing the span.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the field values of the span, in the order of [`Metadata::fields`].
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Returns an iterator over the field names and values of the span.
+    pub fn fields(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.metadata.fields().iter().zip(&self.values)
+    }
+}
+
+/// A period of time in which events occurred, such as the handling of a request.
+///
+/// Spans are created with the [`span!`] macro, or one of [`error_span!`],
+/// [`warn_span!`], [`info_span!`], [`debug_span!`], [`trace_span!`]. While the
+/// guard returned by [`Span::enter`] is alive, every event emitted by the
+/// process carries the span's context. The span is closed when dropped.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{info, info_span};
+///
+/// # fn main() {
+/// let span = info_span!("request", request_id = 42);
+/// let _guard = span.enter();
+///
+/// info!("Handling request");
+/// # }
+/// ```
+///
+/// A span can be the parent of spans created in other processes by sending
+/// its [`SpanId`]:
+///
+/// ```
+/// use lunatic::spawn_link;
+/// use lunatic_log::{info, info_span};
+///
+/// # fn main() {
+/// let span = info_span!("request");
+/// let parent = span.id();
+/// spawn_link!(|parent, _mailbox: Mailbox<()>| {
+///     let span = info_span!(parent: parent, "worker");
+///     let _guard = span.enter();
+///     info!("Working");
+/// });
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Span {
+    context: SpanContext,
+    enabled: bool,
+}
+
+impl Span {
+    /// Creates a new span and notifies the subscriber.
+    ///
+    /// If the subscriber is not interested in the span, it is disabled: it is
+    /// not sent to the subscriber and not attached to events.
+    ///
+    /// You should use the [`span!`] macro instead.
+    pub fn new(metadata: Metadata, values: Vec<Value>, parent: Option<SpanId>) -> Self {
+        let proc = if *metadata.level() <= STATIC_MAX_LEVEL {
+            crate::__lookup_enabled_logging_process(
+                *metadata.level(),
+                metadata.target(),
+                metadata.module_path().map_or("", String::as_str),
+            )
+        } else {
+            None
+        };
+        let context = SpanContext {
+            id: SpanId::next(parent),
+            parent,
+            metadata,
+            values,
+        };
+        if let Some(proc) = &proc {
+            crate::buffer::send_buffered();
+            proc.send(Message::NewSpan(context.clone()));
+        }
+        Span {
+            context,
+            enabled: proc.is_some(),
+        }
+    }
+
+    /// Creates a disabled span, without notifying the subscriber.
+    ///
+    /// This is what the [`span!`] macro returns when the subscriber is not
+    /// interested in the span, without evaluating its fields.
+    pub const fn none() -> Self {
+        Span {
+            context: SpanContext {
+                id: SpanId::from_u128(0),
+                parent: None,
+                metadata: Metadata::new(
+                    String::new(),
+                    String::new(),
+                    Level::Trace,
+                    Vec::new(),
+                    None,
+                    None,
+                    None,
+                ),
+                values: Vec::new(),
+            },
+            enabled: false,
+        }
+    }
+
+    /// Returns the id of the span.
+    pub fn id(&self) -> SpanId {
+        self.context.id
+    }
+
+    /// Returns the context of the span.
+    pub fn context(&self) -> &SpanContext {
+        &self.context
+    }
+
+    /// Returns `true` if the span is sent to the subscriber.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enters the span, returning a guard that exits it when dropped.
+    pub fn enter(&self) -> Entered<'_> {
+        self.enter_span();
+        Entered { span: self }
+    }
+
+    /// Pushes the span onto the stack of entered spans and notifies the subscriber.
+    pub(crate) fn enter_span(&self) {
+        if !self.enabled {
+            return;
+        }
+        SPAN_STACK.with(|stack| stack.borrow_mut().push(self.context.clone()));
+        crate::buffer::send_expired();
+        if let Some(proc) = crate::__lookup_logging_process() {
+            proc.send(Message::Enter(self.id()));
+        }
+    }
+
+    /// Removes the span from the stack of entered spans and notifies the subscriber.
+    pub(crate) fn exit_span(&self) {
+        if !self.enabled {
+            return;
+        }
+        let id = self.id();
+        SPAN_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            if let Some(index) = stack.iter().rposition(|span| span.id == id) {
+                stack.remove(index);
+            }
+        });
+        crate::buffer::send_expired();
+        if let Some(proc) = crate::__lookup_logging_process() {
+            proc.send(Message::Exit(id));
+        }
+    }
+
+    /// Executes the given function in the context of this span.
+    pub fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _entered = self.enter();
+        f()
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        crate::buffer::send_buffered();
+        if let Some(proc) = crate::__lookup_logging_process() {
+            proc.send(Message::Close(self.id()));
+        }
+    }
+}
+
+/// A guard representing a span which has been entered.
+///
+/// The span is exited when the guard is dropped.
+#[derive(Debug)]
+#[must_use = "once a span has been entered, it should be exited"]
+pub struct Entered<'a> {
+    span: &'a Span,
+}
+
+impl Drop for Entered<'_> {
+    fn drop(&mut self) {
+        self.span.exit_span();
+    }
+}
+
+/// Returns the id of the innermost span entered by the current process.
+pub fn current_span() -> Option<SpanId> {
+    SPAN_STACK.with(|stack| stack.borrow().last().map(|span| span.id))
+}
+
+pub(crate) fn current_spans() -> Vec<SpanContext> {
+    SPAN_STACK.with(|stack| stack.borrow().clone())
+}
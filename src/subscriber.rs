@@ -3,12 +3,28 @@
 //! It can be used to print to stdout with [`FmtSubscriber`](fmt::FmtSubscriber),
 //! but is also capable of handling logs in other ways.
 
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub mod archive;
+mod batch;
+pub mod elasticsearch;
+pub mod file;
 pub mod fmt;
+pub mod gelf;
+mod http;
+pub mod layer;
+pub mod loki;
 pub mod multiple;
+pub mod otlp;
+pub mod syslog;
+mod transport;
 
-use serde::{de::DeserializeOwned, Serialize};
+use std::cmp;
 
-use crate::{Event, Metadata};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{filter::EnvFilter, Event, Level, LevelFilter, Metadata, SpanContext, SpanId};
+
+use self::layer::{Layer, Layered};
 
 /// A subscriber which handles incoming log [`Event`]s.
 ///
@@ -36,4 +52,168 @@ pub trait Subscriber: Serialize + DeserializeOwned {
 
     /// Handle a log [`Event`].
     fn event(&self, event: &Event);
+
+    /// Handle a newly created span.
+    fn new_span(&self, _span: &SpanContext) {}
+
+    /// Handle a span being entered.
+    fn enter(&self, _id: &SpanId) {}
+
+    /// Handle a span being exited.
+    fn exit(&self, _id: &SpanId) {}
+
+    /// Handle a span being closed.
+    fn close(&self, _id: &SpanId) {}
+
+    /// Describes which events the subscriber may be interested in.
+    ///
+    /// The interest is cached by emitting processes, which skip formatting and
+    /// sending events that are not part of it. Defaults to [`Interest::always`].
+    fn interest(&self) -> Interest {
+        Interest::always()
+    }
+
+    /// Replace the filter of the subscriber.
+    ///
+    /// This is called when [`set_filter`](crate::set_filter) is used. Subscribers
+    /// without a configurable filter ignore it.
+    fn set_filter(&mut self, _filter: EnvFilter) {}
+
+    /// Flush any buffered output.
+    ///
+    /// This is called when [`flush`](crate::flush) or [`shutdown`](crate::shutdown) is used.
+    fn flush(&self) {}
+
+    /// Wraps the subscriber with a [`Layer`], which sees every event before it.
+    ///
+    /// Layers run inside the subscriber process, so filters and enrichers can
+    /// be stacked without spawning a process per layer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lunatic_log::{
+    ///     filter::EnvFilter,
+    ///     subscriber::{fmt::FmtSubscriber, layer::Fields, Subscriber},
+    ///     LevelFilter,
+    /// };
+    ///
+    /// let filter: EnvFilter = "info,my_app::db=debug".parse().unwrap();
+    /// lunatic_log::init(
+    ///     FmtSubscriber::new(LevelFilter::Trace)
+    ///         .with(filter)
+    ///         .with(Fields::new().with("service", "api")),
+    /// );
+    /// ```
+    fn with<L: Layer>(self, layer: L) -> Layered<L, Self>
+    where
+        Self: Sized,
+    {
+        Layered::new(layer, self)
+    }
+}
+
+/// Describes the events a [`Subscriber`] may be interested in.
+///
+/// Events at a level more verbose than the interest's level for their target
+/// are discarded by the emitting process, before being formatted and sent.
+/// Target levels apply to the target and its submodules, with the longest
+/// matching target taking precedence.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{subscriber::Interest, Level, LevelFilter};
+///
+/// let interest = Interest::max_level(LevelFilter::Info)
+///     .with_target("my_app::db", LevelFilter::Debug);
+///
+/// assert!(interest.enabled(Level::Debug, "my_app::db::pool"));
+/// assert!(!interest.enabled(Level::Debug, "my_app::http"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interest {
+    max_level: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Interest {
+    /// An interest in all events.
+    pub fn always() -> Self {
+        Interest::max_level(LevelFilter::Trace)
+    }
+
+    /// An interest in no events.
+    pub fn never() -> Self {
+        Interest::max_level(LevelFilter::Off)
+    }
+
+    /// An interest in events up to a level of verbosity.
+    pub fn max_level(max_level: LevelFilter) -> Self {
+        Interest {
+            max_level,
+            targets: Vec::new(),
+        }
+    }
+
+    /// Sets the level of verbosity for a target and its submodules.
+    pub fn with_target(mut self, target: impl Into<String>, level: LevelFilter) -> Self {
+        let target = target.into();
+        match self.targets.iter_mut().find(|(t, _)| *t == target) {
+            Some((_, l)) => *l = level,
+            None => self.targets.push((target, level)),
+        }
+        self
+    }
+
+    /// Returns the most verbose level of any target.
+    pub fn max_level_hint(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.max_level, cmp::max)
+    }
+
+    /// Returns the level of verbosity for a target.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(t, _)| target_matches(t, target))
+            .max_by_key(|(t, _)| t.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.max_level)
+    }
+
+    /// Returns `true` if events with the level and target are part of the interest.
+    pub fn enabled(&self, level: Level, target: &str) -> bool {
+        level <= self.level_for(target)
+    }
+
+    /// Combines two interests, so that only events part of both are part of the result.
+    pub fn intersection(&self, other: &Interest) -> Interest {
+        let mut intersection = Interest::max_level(cmp::min(self.max_level, other.max_level));
+        for (target, _) in self.targets.iter().chain(&other.targets) {
+            let level = cmp::min(self.level_for(target), other.level_for(target));
+            intersection = intersection.with_target(target.clone(), level);
+        }
+        intersection
+    }
+
+    /// Combines two interests, so that events part of either are part of the result.
+    pub fn union(&self, other: &Interest) -> Interest {
+        let mut union = Interest::max_level(cmp::max(self.max_level, other.max_level));
+        for (target, _) in self.targets.iter().chain(&other.targets) {
+            let level = cmp::max(self.level_for(target), other.level_for(target));
+            union = union.with_target(target.clone(), level);
+        }
+        union
+    }
+}
+
+/// Returns `true` if `target` is `prefix` or one of its submodules.
+pub(crate) fn target_matches(prefix: &str, target: &str) -> bool {
+    match target.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
 }
@@ -0,0 +1,561 @@
+//! Subscriber that writes compressed log archives.
+//!
+//! Events are written to compressed segment files in a directory, named with
+//! a prefix and the time the segment was opened, for example
+//! `app.2024-05-01T13-00-00.123.log.gz`. A segment is closed when it reaches a
+//! size or age limit, and closed segments are deleted by a retention policy.
+//! The retention policy is applied when the first event is written, whenever
+//! a segment is closed, and every minute while events are written.
+//!
+//! Events are compressed as they are written, so closing a segment only
+//! flushes the end of the stream and never compresses a whole file at once.
+//!
+//! This module requires the `gzip` or `zstd` feature.
+
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{filter::EnvFilter, Event, LevelFilter, Metadata};
+
+use super::{
+    fmt::{writer::MakeWriter, FmtSubscriber},
+    Interest, Subscriber,
+};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S%.3f";
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A subscriber writing formatted events to compressed segment files.
+///
+/// Events are formatted by a [`FmtSubscriber`], which prints the time, level
+/// and target by default.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use lunatic_log::{
+///     subscriber::archive::{ArchiveSubscriber, Compression},
+///     LevelFilter,
+/// };
+///
+/// lunatic_log::init(
+///     ArchiveSubscriber::new("logs", "app", Compression::Gzip, LevelFilter::Info)
+///         .with_max_segment_size(64 * 1024 * 1024)
+///         .with_max_segment_age(Duration::from_secs(60 * 60))
+///         .with_max_total_size(1024 * 1024 * 1024)
+///         .with_max_age(Duration::from_secs(7 * 24 * 60 * 60)),
+/// );
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct ArchiveSubscriber {
+    fmt: FmtSubscriber<ArchiveWriter>,
+}
+
+impl ArchiveSubscriber {
+    /// Creates an instance of [`ArchiveSubscriber`] writing segments named
+    /// with `prefix` to the directory `dir`.
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        prefix: impl Into<String>,
+        compression: Compression,
+        level_filter: LevelFilter,
+    ) -> Self {
+        let fmt = FmtSubscriber::new(level_filter)
+            .with_time(true)
+            .with_level(true)
+            .with_target(true);
+        ArchiveSubscriber {
+            fmt: fmt.with_writer(ArchiveWriter::new(dir, prefix, compression)),
+        }
+    }
+
+    /// Formats events with a configured [`FmtSubscriber`], such as one using
+    /// [`json`](FmtSubscriber::json), replacing its filter and writer.
+    pub fn with_format(self, fmt: FmtSubscriber) -> Self {
+        let filter = self.fmt.filter().clone();
+        let writer = self.fmt.into_writer();
+        ArchiveSubscriber {
+            fmt: fmt.with_env_filter(filter).with_writer(writer),
+        }
+    }
+
+    /// Filter logs with an [`EnvFilter`], replacing the level filter.
+    pub fn with_env_filter(mut self, filter: EnvFilter) -> Self {
+        self.fmt = self.fmt.with_env_filter(filter);
+        self
+    }
+
+    /// Closes a segment once this many uncompressed bytes were written to it.
+    pub fn with_max_segment_size(mut self, max_segment_size: u64) -> Self {
+        self.fmt.writer_mut().max_segment_size = Some(max_segment_size);
+        self
+    }
+
+    /// Closes a segment once it has been open for this long.
+    pub fn with_max_segment_age(mut self, max_segment_age: Duration) -> Self {
+        self.fmt.writer_mut().max_segment_age = Some(max_segment_age);
+        self
+    }
+
+    /// Deletes the oldest segments while all segments take more bytes than this.
+    pub fn with_max_total_size(mut self, max_total_size: u64) -> Self {
+        self.fmt.writer_mut().max_total_size = Some(max_total_size);
+        self
+    }
+
+    /// Deletes segments closed longer ago than this.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.fmt.writer_mut().max_age = Some(max_age);
+        self
+    }
+}
+
+impl Subscriber for ArchiveSubscriber {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.fmt.enabled(metadata)
+    }
+
+    fn event(&self, event: &Event) {
+        self.fmt.event(event);
+    }
+
+    fn interest(&self) -> Interest {
+        self.fmt.interest()
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.fmt.set_filter(filter);
+    }
+
+    fn flush(&self) {
+        self.fmt.flush();
+    }
+}
+
+/// The compression format of segment files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+    /// Gzip, with the `.log.gz` extension.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Zstandard, with the `.log.zst` extension.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    fn extension(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => ".log.gz",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => ".log.zst",
+        }
+    }
+
+    fn encoder(&self, file: File) -> io::Result<Encoder> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Encoder::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            ))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Encoder::Zstd(
+                zstd::Encoder::new(file, zstd::DEFAULT_COMPRESSION_LEVEL)?.auto_finish(),
+            )),
+        }
+    }
+}
+
+/// A compressing stream, which writes the end of the stream when dropped.
+enum Encoder {
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<File>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::AutoFinishEncoder<'static, File>),
+}
+
+impl io::Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// A [`MakeWriter`] writing to compressed segment files.
+///
+/// This is the writer used by [`ArchiveSubscriber`], and can be passed to
+/// [`FmtSubscriber::with_writer`] directly.
+#[derive(Serialize, Deserialize)]
+pub struct ArchiveWriter {
+    dir: PathBuf,
+    prefix: String,
+    compression: Compression,
+    max_segment_size: Option<u64>,
+    max_segment_age: Option<Duration>,
+    max_total_size: Option<u64>,
+    max_age: Option<Duration>,
+    #[serde(skip)]
+    segment: RefCell<Option<Segment>>,
+    /// When the retention policy was last applied.
+    #[serde(skip)]
+    retained: Cell<Option<SystemTime>>,
+    #[serde(skip)]
+    failed: Cell<bool>,
+}
+
+struct Segment {
+    encoder: Encoder,
+    path: PathBuf,
+    opened: SystemTime,
+    size: u64,
+}
+
+impl ArchiveWriter {
+    /// Creates an [`ArchiveWriter`] writing segments named with `prefix` to the
+    /// directory `dir`, which are never closed or deleted.
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        prefix: impl Into<String>,
+        compression: Compression,
+    ) -> Self {
+        ArchiveWriter {
+            dir: dir.into(),
+            prefix: prefix.into(),
+            compression,
+            max_segment_size: None,
+            max_segment_age: None,
+            max_total_size: None,
+            max_age: None,
+            segment: RefCell::new(None),
+            retained: Cell::new(None),
+            failed: Cell::new(false),
+        }
+    }
+
+    /// Closes a segment once this many uncompressed bytes were written to it.
+    pub fn with_max_segment_size(mut self, max_segment_size: u64) -> Self {
+        self.max_segment_size = Some(max_segment_size);
+        self
+    }
+
+    /// Closes a segment once it has been open for this long.
+    pub fn with_max_segment_age(mut self, max_segment_age: Duration) -> Self {
+        self.max_segment_age = Some(max_segment_age);
+        self
+    }
+
+    /// Deletes the oldest segments while all segments take more bytes than this.
+    pub fn with_max_total_size(mut self, max_total_size: u64) -> Self {
+        self.max_total_size = Some(max_total_size);
+        self
+    }
+
+    /// Deletes segments closed longer ago than this.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Returns the open segment, closing it and opening a new one first if needed.
+    ///
+    /// Failing to apply the retention policy is reported, but doesn't stop
+    /// events from being written.
+    fn current(&self, now: SystemTime) -> io::Result<RefMut<'_, Segment>> {
+        let mut segment = self.segment.borrow_mut();
+        let close = match &*segment {
+            Some(segment) => {
+                self.max_segment_size
+                    .is_some_and(|max_size| segment.size >= max_size)
+                    || self.max_segment_age.is_some_and(|max_age| {
+                        now.duration_since(segment.opened).unwrap_or_default() >= max_age
+                    })
+            }
+            None => false,
+        };
+        if close {
+            // Dropping the encoder writes the end of the compressed stream.
+            *segment = None;
+        }
+        let retention_due = !self.retained.get().is_some_and(|retained| {
+            now.duration_since(retained).unwrap_or_default() < RETENTION_INTERVAL
+        });
+        if close || retention_due {
+            let open = segment.as_ref().map(|segment| segment.path.as_path());
+            if let Err(err) = self.apply_retention(now, open) {
+                eprintln!(
+                    "lunatic-log: failed to apply retention to {}: {err}",
+                    self.dir.display()
+                );
+            }
+        }
+        if segment.is_none() {
+            *segment = Some(self.open(now)?);
+        }
+        Ok(RefMut::map(segment, |segment| segment.as_mut().unwrap()))
+    }
+
+    fn open(&self, now: SystemTime) -> io::Result<Segment> {
+        fs::create_dir_all(&self.dir)?;
+        let timestamp = DateTime::<Utc>::from(now).format(TIMESTAMP_FORMAT);
+        let extension = self.compression.extension();
+        let mut path = self
+            .dir
+            .join(format!("{}.{timestamp}{extension}", self.prefix));
+        let mut n = 1;
+        while path.exists() {
+            path = self
+                .dir
+                .join(format!("{}.{timestamp}-{n}{extension}", self.prefix));
+            n += 1;
+        }
+        let file = File::create(&path)?;
+        Ok(Segment {
+            encoder: self.compression.encoder(file)?,
+            path,
+            opened: now,
+            size: 0,
+        })
+    }
+
+    /// Deletes the closed segments exceeding the total size or age limits. The
+    /// open segment counts towards the total size, but is never deleted.
+    fn apply_retention(&self, now: SystemTime, open: Option<&Path>) -> io::Result<()> {
+        self.retained.set(Some(now));
+        if self.max_total_size.is_none() && self.max_age.is_none() {
+            return Ok(());
+        }
+        let prefix = format!("{}.", self.prefix);
+        let extension = self.compression.extension();
+        let mut segments = Vec::new();
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            // Nothing was written yet.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with(&prefix) && name.ends_with(extension) {
+                let metadata = entry.metadata()?;
+                segments.push((metadata.modified()?, metadata.len(), entry.path()));
+            }
+        }
+        // Newest first, so the oldest segments are deleted once over the limit.
+        segments.sort_by(|a, b| b.cmp(a));
+        let mut total_size = 0;
+        for (modified, size, path) in segments {
+            total_size += size;
+            if Some(path.as_path()) == open {
+                continue;
+            }
+            let too_large = self.max_total_size.is_some_and(|max| total_size > max);
+            let too_old = self
+                .max_age
+                .is_some_and(|max_age| now.duration_since(modified).unwrap_or_default() > max_age);
+            if too_large || too_old {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MakeWriter for ArchiveWriter {
+    fn make_writer(&self, _metadata: &Metadata) -> Box<dyn io::Write + '_> {
+        match self.current(SystemTime::now()) {
+            Ok(segment) => {
+                self.failed.set(false);
+                Box::new(SegmentWriter(segment))
+            }
+            Err(err) => {
+                // Only report the first of consecutive failures.
+                if !self.failed.replace(true) {
+                    eprintln!(
+                        "lunatic-log: failed to write archive to {}: {err}",
+                        self.dir.display()
+                    );
+                }
+                Box::new(io::sink())
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(segment) = &mut *self.segment.borrow_mut() {
+            let _ = io::Write::flush(&mut segment.encoder);
+        }
+    }
+}
+
+/// Writes to the open segment, keeping track of the uncompressed size.
+struct SegmentWriter<'a>(RefMut<'a, Segment>);
+
+impl io::Write for SegmentWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.0.encoder.write(buf)?;
+        self.0.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.encoder.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lunatic-log-archive-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn time(second: u32) -> SystemTime {
+        Utc.with_ymd_and_hms(2024, 5, 1, 13, 0, second)
+            .unwrap()
+            .into()
+    }
+
+    fn write(writer: &ArchiveWriter, now: SystemTime, line: &str) {
+        let segment = writer.current(now).unwrap();
+        SegmentWriter(segment).write_all(line.as_bytes()).unwrap();
+    }
+
+    /// Closes the open segment, returning its path.
+    fn close(writer: &ArchiveWriter) -> PathBuf {
+        writer.segment.take().unwrap().path
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn round_trips_gzip() {
+        use std::io::Read;
+
+        let dir = temp_dir("gzip");
+        let writer = ArchiveWriter::new(&dir, "app", Compression::Gzip);
+        write(&writer, time(0), "first\n");
+        write(&writer, time(1), "second\n");
+        let path = close(&writer);
+        assert_eq!(files(&dir), ["app.2024-05-01T13-00-00.000.log.gz"]);
+
+        let mut lines = String::new();
+        flate2::read::GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut lines)
+            .unwrap();
+        assert_eq!(lines, "first\nsecond\n");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn round_trips_zstd() {
+        let dir = temp_dir("zstd");
+        let writer = ArchiveWriter::new(&dir, "app", Compression::Zstd);
+        write(&writer, time(0), "first\n");
+        write(&writer, time(1), "second\n");
+        let path = close(&writer);
+        assert_eq!(files(&dir), ["app.2024-05-01T13-00-00.000.log.zst"]);
+
+        let lines = zstd::decode_all(File::open(path).unwrap()).unwrap();
+        assert_eq!(String::from_utf8(lines).unwrap(), "first\nsecond\n");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn closes_segments_by_size() {
+        let dir = temp_dir("segment-size");
+        let writer = ArchiveWriter::new(&dir, "app", Compression::Gzip).with_max_segment_size(1);
+        write(&writer, time(0), "first\n");
+        write(&writer, time(1), "second\n");
+        assert_eq!(
+            files(&dir),
+            [
+                "app.2024-05-01T13-00-00.000.log.gz",
+                "app.2024-05-01T13-00-01.000.log.gz",
+            ]
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn deletes_segments_over_total_size() {
+        let dir = temp_dir("total-size");
+        let writer = ArchiveWriter::new(&dir, "app", Compression::Gzip)
+            .with_max_segment_size(1)
+            .with_max_total_size(0);
+        for second in 0..3 {
+            write(&writer, time(second), "event\n");
+        }
+        // Only the open segment is kept.
+        assert_eq!(files(&dir), ["app.2024-05-01T13-00-02.000.log.gz"]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn deletes_segments_over_max_age() {
+        let dir = temp_dir("max-age");
+        fs::create_dir_all(&dir).unwrap();
+        let now = time(0);
+        let hour = Duration::from_secs(60 * 60);
+        for (name, age) in [("old", 48 * hour), ("recent", hour)] {
+            let file = File::create(dir.join(format!("app.{name}.log.gz"))).unwrap();
+            file.set_modified(now - age).unwrap();
+        }
+        let other = dir.join("other.old.log.gz");
+        File::create(&other)
+            .unwrap()
+            .set_modified(now - 48 * hour)
+            .unwrap();
+
+        let writer = ArchiveWriter::new(&dir, "app", Compression::Gzip).with_max_age(24 * hour);
+        write(&writer, now, "event\n");
+        assert_eq!(
+            files(&dir),
+            [
+                "app.2024-05-01T13-00-00.000.log.gz",
+                "app.recent.log.gz",
+                "other.old.log.gz",
+            ]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Batching of events, shared by the subscribers pushing to HTTP APIs.
+//!
+//! Items are collected and sent by a worker process linked to the subscriber
+//! process, so that waiting for a slow server or between retries never stops
+//! the subscriber process from handling events. Only a [flush](crate::flush)
+//! waits for the worker process, and for at most 10 seconds.
+
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+use lunatic::{spawn_link, Mailbox, MailboxResult, Process, Tag};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Sends batches of items to an HTTP API.
+pub(crate) trait Export: Clone + Serialize + DeserializeOwned {
+    /// An item of a batch, prepared by the subscriber process.
+    type Item: Serialize + DeserializeOwned;
+
+    /// Describes what failed, followed by the URL in error messages, such as
+    /// `failed to push logs to`.
+    const FAILURE: &'static str;
+
+    /// Returns the URL batches are sent to.
+    fn url(&self) -> String;
+
+    /// Sends a batch of items.
+    fn export(&self, items: Vec<Self::Item>) -> Result<(), ExportError<Self::Item>>;
+}
+
+/// The error returned when a batch, or part of it, was not sent.
+pub(crate) struct ExportError<T> {
+    pub(crate) error: String,
+    /// The items to send again after a backoff.
+    pub(crate) retry: Vec<T>,
+    /// Whether items were discarded without being retried.
+    pub(crate) discarded: bool,
+}
+
+impl<T> ExportError<T> {
+    /// An error after which the whole batch is sent again.
+    pub(crate) fn retry(error: impl Into<String>, items: Vec<T>) -> Self {
+        ExportError {
+            error: error.into(),
+            retry: items,
+            discarded: false,
+        }
+    }
+
+    /// An error after which the whole batch is discarded.
+    pub(crate) fn discard(error: impl Into<String>) -> Self {
+        ExportError {
+            error: error.into(),
+            retry: Vec::new(),
+            discarded: true,
+        }
+    }
+}
+
+/// The limits of batches and retries.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Batching {
+    /// A batch is sent once it holds this many items.
+    pub(crate) max_size: usize,
+    /// A batch is sent this long after its first item was added.
+    pub(crate) max_age: Duration,
+    /// How many times a failed batch is sent again before it is discarded.
+    pub(crate) max_retries: u32,
+    /// How long to wait before the first retry, doubling after every retry.
+    pub(crate) initial_backoff: Duration,
+}
+
+/// Collects items in a worker process, which sends them in batches.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Batcher<E: Export> {
+    exporter: E,
+    batching: Batching,
+    #[serde(skip)]
+    worker: RefCell<Option<Process<WorkerMessage<E::Item>>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum WorkerMessage<T> {
+    Item(T),
+    Flush(Process<()>, Tag),
+    Stop,
+}
+
+impl<E: Export> Batcher<E> {
+    pub(crate) fn new(exporter: E, batching: Batching) -> Self {
+        Batcher {
+            exporter,
+            batching,
+            worker: RefCell::new(None),
+        }
+    }
+
+    pub(crate) fn exporter_mut(&mut self) -> &mut E {
+        &mut self.exporter
+    }
+
+    pub(crate) fn batching_mut(&mut self) -> &mut Batching {
+        &mut self.batching
+    }
+
+    /// Adds an item to the batch, starting the worker process if needed.
+    pub(crate) fn push(&self, item: E::Item) {
+        let mut worker = self.worker.borrow_mut();
+        let worker = worker.get_or_insert_with(|| {
+            let exporter = self.exporter.clone();
+            let batching = self.batching.clone();
+            spawn_link!(
+                |exporter, batching, mailbox: Mailbox<WorkerMessage<E::Item>>| {
+                    run_worker(exporter, batching, mailbox)
+                }
+            )
+        });
+        worker.send(WorkerMessage::Item(item));
+    }
+
+    /// Waits until the worker process has sent every item added before, or
+    /// gives up after [`REQUEST_TIMEOUT`](crate::REQUEST_TIMEOUT) while it is
+    /// retrying.
+    pub(crate) fn flush(&self) {
+        if let Some(worker) = &*self.worker.borrow() {
+            let tag = Tag::new();
+            worker.send(WorkerMessage::Flush(Process::this(), tag));
+            // Safety: only the reply carrying `tag` is received, which is always a `()`.
+            let mailbox: Mailbox<()> = unsafe { Mailbox::new() };
+            let _ = mailbox.tag_receive_timeout(&[tag], crate::REQUEST_TIMEOUT);
+        }
+    }
+}
+
+impl<E: Export> Drop for Batcher<E> {
+    /// Stops the worker process once it has sent the remaining items, since it
+    /// outlives a subscriber process that stopped normally.
+    fn drop(&mut self) {
+        if let Some(worker) = self.worker.get_mut() {
+            worker.send(WorkerMessage::Stop);
+        }
+    }
+}
+
+fn run_worker<E: Export>(
+    exporter: E,
+    batching: Batching,
+    mailbox: Mailbox<WorkerMessage<E::Item>>,
+) {
+    let mut worker = Worker {
+        exporter,
+        batching,
+        items: Vec::new(),
+        started: Instant::now(),
+        failed: false,
+    };
+    loop {
+        let message = if worker.items.is_empty() {
+            Some(mailbox.receive())
+        } else {
+            let timeout = worker
+                .batching
+                .max_age
+                .saturating_sub(worker.started.elapsed());
+            match mailbox.receive_timeout(timeout) {
+                MailboxResult::Message(message) => Some(message),
+                _ => None,
+            }
+        };
+        match message {
+            Some(WorkerMessage::Item(item)) => {
+                if worker.items.is_empty() {
+                    worker.started = Instant::now();
+                }
+                worker.items.push(item);
+                if worker.items.len() >= worker.batching.max_size {
+                    worker.send();
+                }
+            }
+            Some(WorkerMessage::Flush(proc, tag)) => {
+                worker.send();
+                proc.tag_send(tag, ());
+            }
+            Some(WorkerMessage::Stop) => {
+                worker.send();
+                return;
+            }
+            // The batch reached its maximum age.
+            None => worker.send(),
+        }
+    }
+}
+
+/// The state of a worker process.
+struct Worker<E: Export> {
+    exporter: E,
+    batching: Batching,
+    items: Vec<E::Item>,
+    started: Instant,
+    failed: bool,
+}
+
+impl<E: Export> Worker<E> {
+    /// Sends the batch, if it isn't empty, retrying with exponential backoff.
+    ///
+    /// Discarded items are reported once for consecutive failures.
+    fn send(&mut self) {
+        let mut items = std::mem::take(&mut self.items);
+        let mut backoff = self.batching.initial_backoff;
+        let mut retries = 0;
+        while !items.is_empty() {
+            let error = match self.exporter.export(items) {
+                Ok(()) => {
+                    self.failed = false;
+                    return;
+                }
+                Err(error) => error,
+            };
+            items = error.retry;
+            let give_up = retries >= self.batching.max_retries;
+            let discarded = error.discarded || (give_up && !items.is_empty());
+            if discarded && !std::mem::replace(&mut self.failed, true) {
+                eprintln!(
+                    "lunatic-log: {} {}: {}",
+                    E::FAILURE,
+                    self.exporter.url(),
+                    error.error
+                );
+            }
+            if give_up {
+                return;
+            }
+            if !items.is_empty() {
+                lunatic::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                retries += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Subscriber that indexes events in Elasticsearch or OpenSearch.
+//!
+//! Events are sent in batches to the [`_bulk`] API as newline-delimited JSON,
+//! into an index named after the time of each event, such as
+//! `logs-2024.05.01`. The bulk response is checked for every event, and
+//! events rejected because the cluster is overloaded are retried with
+//! exponential backoff.
+//!
+//! Batches are sent by a worker process linked to the subscriber process, so
+//! waiting for the cluster and between retries blocks neither the processes
+//! emitting events nor the subscriber process.
+//!
+//! A batch is sent when it holds enough events, when it reaches its maximum
+//! age, or when the subscriber is [flushed](crate::flush).
+//!
+//! [`_bulk`]: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html
+
+use std::{collections::HashMap, fmt::Write, time::Duration};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Utc,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{filter::EnvFilter, Event, LevelFilter, Metadata};
+
+use super::{
+    batch::{Batcher, Batching, Export, ExportError},
+    fmt::{json_fields, json_spans},
+    http::HttpClient,
+    Interest, Subscriber,
+};
+
+const DEFAULT_INDEX: &str = "logs-%Y.%m.%d";
+const DEFAULT_MAX_BATCH_SIZE: usize = 1024;
+const DEFAULT_MAX_BATCH_AGE: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// A subscriber indexing events with the bulk API of Elasticsearch or OpenSearch.
+///
+/// Each event is indexed as a document with the [ECS] fields `@timestamp`,
+/// `message`, `log.level`, `log.logger`, `log.origin.file.name` and
+/// `log.origin.file.line`, along with its fields and spans.
+///
+/// [ECS]: https://www.elastic.co/guide/en/ecs/current/index.html
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{subscriber::elasticsearch::ElasticsearchSubscriber, LevelFilter};
+///
+/// lunatic_log::init(
+///     ElasticsearchSubscriber::new("http://127.0.0.1:9200", LevelFilter::Info)
+///         .with_index("my-app-%Y.%m.%d"),
+/// );
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct ElasticsearchSubscriber {
+    filter: EnvFilter,
+    index: String,
+    batcher: Batcher<Bulk>,
+}
+
+/// Sends batches of documents to the bulk API.
+#[derive(Clone, Serialize, Deserialize)]
+struct Bulk {
+    client: HttpClient,
+}
+
+/// A document to be indexed, with the name of its index.
+#[derive(Debug, Serialize, Deserialize)]
+struct Document {
+    index: String,
+    source: String,
+}
+
+impl ElasticsearchSubscriber {
+    /// Creates an instance of [`ElasticsearchSubscriber`] sending to the
+    /// cluster at `url`.
+    ///
+    /// The url is an `http://` URL, with the path defaulting to `/_bulk`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the URL has another scheme, such as `https://`.
+    pub fn new(url: impl AsRef<str>, level_filter: LevelFilter) -> Self {
+        let bulk = Bulk {
+            client: HttpClient::new(url.as_ref(), "/_bulk"),
+        };
+        let batching = Batching {
+            max_size: DEFAULT_MAX_BATCH_SIZE,
+            max_age: DEFAULT_MAX_BATCH_AGE,
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+        };
+        ElasticsearchSubscriber {
+            filter: level_filter.into(),
+            index: DEFAULT_INDEX.to_string(),
+            batcher: Batcher::new(bulk, batching),
+        }
+    }
+
+    /// Filter logs with an [`EnvFilter`], replacing the level filter.
+    pub fn with_env_filter(mut self, filter: EnvFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the index pattern, `logs-%Y.%m.%d` by default.
+    ///
+    /// The pattern is formatted with the UTC time of each event, in the
+    /// `strftime` format supported by [chrono](https://docs.rs/chrono/latest/chrono/format/strftime/index.html).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is not a valid `strftime` format.
+    pub fn with_index(mut self, index: impl Into<String>) -> Self {
+        let index = index.into();
+        if StrftimeItems::new(&index).any(|item| item == Item::Error) {
+            panic!("invalid index pattern `{index}`");
+        }
+        self.index = index;
+        self
+    }
+
+    /// Adds a header sent with every request, such as `Authorization`.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let client = &mut self.batcher.exporter_mut().client;
+        client.insert_header(name.into(), value.into());
+        self
+    }
+
+    /// Sends a batch once it holds this many events, 1024 by default.
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.batcher.batching_mut().max_size = max_batch_size.max(1);
+        self
+    }
+
+    /// Sends a batch this long after its first event arrived, 1 second by
+    /// default.
+    pub fn with_max_batch_age(mut self, max_batch_age: Duration) -> Self {
+        self.batcher.batching_mut().max_age = max_batch_age;
+        self
+    }
+
+    /// Sets how many times events are retried before being discarded, 5 by
+    /// default.
+    ///
+    /// Requests are retried when the cluster can't be reached, or responds
+    /// with `429 Too Many Requests` or a server error. Single events are
+    /// retried when the bulk response rejects them with `429 Too Many Requests`.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.batcher.batching_mut().max_retries = max_retries;
+        self
+    }
+
+    /// Sets how long to wait before the first retry, 500 milliseconds by default.
+    ///
+    /// The wait doubles after every retry, up to 30 seconds.
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.batcher.batching_mut().initial_backoff = initial_backoff;
+        self
+    }
+
+    fn document(&self, event: &Event) -> Document {
+        let metadata = event.metadata();
+        let timestamp = DateTime::<Utc>::from(event.timestamp());
+        let mut source = json!({
+            "@timestamp": timestamp.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            "message": event.message(),
+            "log": {
+                "level": metadata.level().as_str().to_lowercase(),
+                "logger": metadata.target(),
+            },
+        });
+        if metadata.file().is_some() || metadata.line().is_some() {
+            source["log"]["origin"] = json!({
+                "file": { "name": metadata.file(), "line": metadata.line() },
+            });
+        }
+        let fields = json_fields(event);
+        if !fields.is_empty() {
+            source["fields"] = fields.into();
+        }
+        if !event.spans().is_empty() {
+            source["spans"] = json_spans(event).into();
+        }
+        Document {
+            index: timestamp.format(&self.index).to_string(),
+            source: source.to_string(),
+        }
+    }
+}
+
+impl Export for Bulk {
+    type Item = Document;
+
+    const FAILURE: &'static str = "failed to index logs at";
+
+    fn url(&self) -> String {
+        self.client.url()
+    }
+
+    /// Sends one bulk request, retrying the documents rejected by the cluster.
+    fn export(&self, documents: Vec<Document>) -> Result<(), ExportError<Document>> {
+        let mut body = String::new();
+        for document in &documents {
+            let action = json!({ "index": { "_index": document.index } });
+            writeln!(body, "{action}\n{}", document.source).unwrap();
+        }
+
+        let response = match self.client.post("application/x-ndjson", body.as_bytes()) {
+            Ok(response) => response,
+            Err(err) => return Err(ExportError::retry(err.to_string(), documents)),
+        };
+        if !response.is_success() {
+            let error = format!(
+                "status {}: {}",
+                response.status,
+                String::from_utf8_lossy(&response.body).trim()
+            );
+            if response.status == 429 || response.status >= 500 {
+                return Err(ExportError::retry(error, documents));
+            }
+            return Err(ExportError::discard(error));
+        }
+
+        let bulk: BulkResponse = match serde_json::from_slice(&response.body) {
+            Ok(bulk) => bulk,
+            Err(err) => {
+                return Err(ExportError::discard(format!(
+                    "invalid bulk response: {err}"
+                )))
+            }
+        };
+        if !bulk.errors {
+            return Ok(());
+        }
+        let mut rejected = Vec::new();
+        let mut failed = 0;
+        let mut first_error = None;
+        for (document, item) in documents.into_iter().zip(bulk.items) {
+            let result = match item.into_values().next() {
+                Some(result) => result,
+                None => continue,
+            };
+            if (200..300).contains(&result.status) {
+                continue;
+            }
+            if result.status == 429 {
+                rejected.push(document);
+            } else {
+                failed += 1;
+            }
+            if first_error.is_none() {
+                first_error = result.error.map(|error| (result.status, error));
+            }
+        }
+        if failed == 0 && rejected.is_empty() {
+            return Ok(());
+        }
+        let mut error = format!(
+            "{} documents discarded, {} rejected",
+            failed,
+            rejected.len()
+        );
+        if let Some((status, item_error)) = first_error {
+            write!(
+                error,
+                ", first error: status {status}: {}: {}",
+                item_error.kind, item_error.reason
+            )
+            .unwrap();
+        }
+        Err(ExportError {
+            error,
+            retry: rejected,
+            discarded: failed > 0,
+        })
+    }
+}
+
+impl Subscriber for ElasticsearchSubscriber {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn event(&self, event: &Event) {
+        self.batcher.push(self.document(event));
+    }
+
+    fn interest(&self) -> Interest {
+        self.filter.interest()
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.filter = filter;
+    }
+
+    fn flush(&self) {
+        self.batcher.flush();
+    }
+}
+
+/// The parts of a bulk API response needed to find failed documents.
+#[derive(Deserialize)]
+struct BulkResponse {
+    errors: bool,
+    #[serde(default)]
+    items: Vec<HashMap<String, BulkItem>>,
+}
+
+/// The result of a single action in a bulk API response.
+#[derive(Deserialize)]
+struct BulkItem {
+    status: u16,
+    error: Option<BulkError>,
+}
+
+#[derive(Deserialize)]
+struct BulkError {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    reason: String,
+}
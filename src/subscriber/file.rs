@@ -0,0 +1,508 @@
+//! Subscriber that appends to rotated files.
+//!
+//! The file is rotated when it reaches a size limit or when a time period,
+//! such as an hour or a day, ends. A rotated file is renamed with a suffix of
+//! the start of the period it was written in, for example
+//! `app.log.2024-05-01T13-00-00` for the hour from 13:00, or of the time it
+//! was rotated if it is never rotated based on time. Only a configured number
+//! of rotated files is kept. Files with the same suffix, such as files rotated
+//! by size within a period, get an additional counter, as in
+//! `app.log.2024-05-01T13-00-00.1`.
+
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{filter::EnvFilter, Event, LevelFilter, Metadata};
+
+use super::{
+    fmt::{writer::MakeWriter, FmtSubscriber},
+    Interest, Subscriber,
+};
+
+const SUFFIX_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+/// A subscriber appending formatted events to a rotated file.
+///
+/// Events are formatted by a [`FmtSubscriber`], which prints the time, level
+/// and target by default.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{
+///     subscriber::file::{FileSubscriber, Rotation},
+///     LevelFilter,
+/// };
+///
+/// lunatic_log::init(
+///     FileSubscriber::new("logs/app.log", LevelFilter::Info)
+///         .with_rotation(Rotation::Daily)
+///         .with_max_size(100 * 1024 * 1024)
+///         .with_max_files(7),
+/// );
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct FileSubscriber {
+    fmt: FmtSubscriber<RollingFile>,
+}
+
+impl FileSubscriber {
+    /// Creates an instance of [`FileSubscriber`] appending to the file at `path`.
+    pub fn new(path: impl Into<PathBuf>, level_filter: LevelFilter) -> Self {
+        let fmt = FmtSubscriber::new(level_filter)
+            .with_time(true)
+            .with_level(true)
+            .with_target(true);
+        FileSubscriber {
+            fmt: fmt.with_writer(RollingFile::new(path)),
+        }
+    }
+
+    /// Formats events with a configured [`FmtSubscriber`], such as one using
+    /// [`json`](FmtSubscriber::json), replacing its filter and writer.
+    pub fn with_format(self, fmt: FmtSubscriber) -> Self {
+        let filter = self.fmt.filter().clone();
+        let writer = self.fmt.into_writer();
+        FileSubscriber {
+            fmt: fmt.with_env_filter(filter).with_writer(writer),
+        }
+    }
+
+    /// Filter logs with an [`EnvFilter`], replacing the level filter.
+    pub fn with_env_filter(mut self, filter: EnvFilter) -> Self {
+        self.fmt = self.fmt.with_env_filter(filter);
+        self
+    }
+
+    /// Rotates the file when a time period ends.
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.fmt.writer_mut().rotation = rotation;
+        self
+    }
+
+    /// Rotates the file when it reaches a size in bytes.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.fmt.writer_mut().max_size = Some(max_size);
+        self
+    }
+
+    /// Keeps at most `max_files` rotated files, deleting the oldest ones.
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.fmt.writer_mut().max_files = Some(max_files);
+        self
+    }
+}
+
+impl Subscriber for FileSubscriber {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.fmt.enabled(metadata)
+    }
+
+    fn event(&self, event: &Event) {
+        self.fmt.event(event);
+    }
+
+    fn interest(&self) -> Interest {
+        self.fmt.interest()
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.fmt.set_filter(filter);
+    }
+
+    fn flush(&self) {
+        self.fmt.flush();
+    }
+}
+
+/// A time period after which a file is rotated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rotation {
+    /// The file is never rotated based on time.
+    Never,
+    /// The file is rotated at the start of every hour.
+    Hourly,
+    /// The file is rotated at the start of every day, in UTC.
+    Daily,
+}
+
+impl Rotation {
+    /// Returns the start of the period containing `time`.
+    fn period(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let date = time.date_naive();
+        let start = match self {
+            Rotation::Never => return None,
+            Rotation::Hourly => date.and_hms_opt(time.hour(), 0, 0)?,
+            Rotation::Daily => date.and_hms_opt(0, 0, 0)?,
+        };
+        Some(Utc.from_utc_datetime(&start))
+    }
+}
+
+/// A [`MakeWriter`] appending to a file, which is rotated by size or time.
+///
+/// This is the writer used by [`FileSubscriber`], and can be passed to
+/// [`FmtSubscriber::with_writer`] directly.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RollingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    max_size: Option<u64>,
+    max_files: Option<usize>,
+    #[serde(skip)]
+    state: RefCell<Option<State>>,
+    #[serde(skip)]
+    failed: Cell<bool>,
+}
+
+#[derive(Debug)]
+struct State {
+    file: File,
+    size: u64,
+    period: Option<DateTime<Utc>>,
+}
+
+impl RollingFile {
+    /// Creates a [`RollingFile`] appending to the file at `path`, which is
+    /// never rotated.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        RollingFile {
+            path: path.into(),
+            rotation: Rotation::Never,
+            max_size: None,
+            max_files: None,
+            state: RefCell::new(None),
+            failed: Cell::new(false),
+        }
+    }
+
+    /// Rotates the file when a time period ends.
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Rotates the file when it reaches a size in bytes.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Keeps at most `max_files` rotated files, deleting the oldest ones.
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+
+    /// Returns the open file, rotating or opening it first if needed.
+    fn current(&self, now: DateTime<Utc>) -> io::Result<RefMut<'_, State>> {
+        let mut state = self.state.borrow_mut();
+        let rotate = match &*state {
+            Some(state) => {
+                self.max_size.is_some_and(|max_size| state.size >= max_size)
+                    || state.period != self.rotation.period(now)
+            }
+            None => false,
+        };
+        if rotate {
+            let period = state.take().and_then(|state| state.period);
+            self.rotate(period.unwrap_or(now))?;
+        }
+        if state.is_none() {
+            *state = Some(self.open(now)?);
+        }
+        Ok(RefMut::map(state, |state| state.as_mut().unwrap()))
+    }
+
+    fn open(&self, now: DateTime<Utc>) -> io::Result<State> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let metadata = file.metadata()?;
+        // An existing file belongs to the period it was last written in.
+        let written = match metadata.len() {
+            0 => now,
+            _ => metadata.modified().map(DateTime::from).unwrap_or(now),
+        };
+        let state = State {
+            file,
+            size: metadata.len(),
+            period: self.rotation.period(written),
+        };
+        if state.size > 0 && state.period != self.rotation.period(now) {
+            let period = state.period;
+            drop(state);
+            self.rotate(period.unwrap_or(now))?;
+            return self.open(now);
+        }
+        Ok(state)
+    }
+
+    /// Renames the file with a suffix of `started`, the start of the period it
+    /// was written in.
+    ///
+    /// Failing to delete old files is reported, but doesn't fail the rotation,
+    /// since the file was already renamed.
+    fn rotate(&self, started: DateTime<Utc>) -> io::Result<()> {
+        let timestamp = started.format(SUFFIX_FORMAT).to_string();
+        let mut rotated = self.rotated_files()?;
+        let counter = rotated
+            .iter()
+            .filter(|file| file.timestamp == timestamp)
+            .map(|file| file.counter + 1)
+            .max();
+        let suffix = match counter {
+            Some(counter) => format!("{timestamp}.{counter}"),
+            None => timestamp.clone(),
+        };
+        let mut path = self.path.as_os_str().to_owned();
+        path.push(".");
+        path.push(suffix);
+        fs::rename(&self.path, &path)?;
+
+        if let Some(max_files) = self.max_files {
+            rotated.push(RotatedFile {
+                timestamp,
+                counter: counter.unwrap_or(0),
+                path: path.into(),
+            });
+            let excess = rotated.len().saturating_sub(max_files);
+            for file in &rotated[..excess] {
+                if let Err(err) = fs::remove_file(&file.path) {
+                    eprintln!(
+                        "lunatic-log: failed to remove {}: {err}",
+                        file.path.display()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the rotated files, from oldest to newest.
+    fn rotated_files(&self) -> io::Result<Vec<RotatedFile>> {
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let prefix = match self.path.file_name() {
+            Some(name) => format!("{}.", name.to_string_lossy()),
+            None => return Ok(Vec::new()),
+        };
+        let mut rotated = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let suffix = match name.strip_prefix(&prefix) {
+                Some(suffix) => suffix,
+                None => continue,
+            };
+            let (timestamp, counter) = match suffix.split_once('.') {
+                Some((timestamp, counter)) => match counter.parse() {
+                    Ok(counter) => (timestamp, counter),
+                    Err(_) => continue,
+                },
+                None => (suffix, 0),
+            };
+            if NaiveDateTime::parse_from_str(timestamp, SUFFIX_FORMAT).is_err() {
+                continue;
+            }
+            rotated.push(RotatedFile {
+                timestamp: timestamp.to_string(),
+                counter,
+                path: entry.path(),
+            });
+        }
+        rotated.sort_by(|a, b| (&a.timestamp, a.counter).cmp(&(&b.timestamp, b.counter)));
+        Ok(rotated)
+    }
+}
+
+#[derive(Debug)]
+struct RotatedFile {
+    timestamp: String,
+    counter: u32,
+    path: PathBuf,
+}
+
+impl MakeWriter for RollingFile {
+    fn make_writer(&self, _metadata: &Metadata) -> Box<dyn io::Write + '_> {
+        match self.current(Utc::now()) {
+            Ok(state) => {
+                self.failed.set(false);
+                Box::new(Appender(state))
+            }
+            Err(err) => {
+                // Only report the first of consecutive failures.
+                if !self.failed.replace(true) {
+                    eprintln!(
+                        "lunatic-log: failed to write to {}: {err}",
+                        self.path.display()
+                    );
+                }
+                Box::new(io::sink())
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(state) = &mut *self.state.borrow_mut() {
+            let _ = io::Write::flush(&mut state.file);
+        }
+    }
+}
+
+/// Appends to the open file, keeping track of its size.
+struct Appender<'a>(RefMut<'a, State>);
+
+impl io::Write for Appender<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.0.file.write(buf)?;
+        self.0.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lunatic-log-file-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn time(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, hour, minute, 0).unwrap()
+    }
+
+    fn write(file: &RollingFile, now: DateTime<Utc>, line: &str) {
+        let state = file.current(now).unwrap();
+        Appender(state).write_all(line.as_bytes()).unwrap();
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn read(dir: &Path, name: &str) -> String {
+        fs::read_to_string(dir.join(name)).unwrap()
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let dir = temp_dir("size");
+        let file = RollingFile::new(dir.join("app.log")).with_max_size(10);
+        write(&file, time(13, 10), "first line\n");
+        write(&file, time(13, 20), "second line\n");
+        assert_eq!(files(&dir), ["app.log", "app.log.2024-05-01T13-20-00"]);
+        assert_eq!(read(&dir, "app.log.2024-05-01T13-20-00"), "first line\n");
+        assert_eq!(read(&dir, "app.log"), "second line\n");
+    }
+
+    #[test]
+    fn rotates_by_size_within_a_period() {
+        let dir = temp_dir("size-period");
+        let file = RollingFile::new(dir.join("app.log"))
+            .with_rotation(Rotation::Hourly)
+            .with_max_size(1);
+        write(&file, time(13, 10), "1\n");
+        write(&file, time(13, 20), "2\n");
+        write(&file, time(13, 30), "3\n");
+        assert_eq!(
+            files(&dir),
+            [
+                "app.log",
+                "app.log.2024-05-01T13-00-00",
+                "app.log.2024-05-01T13-00-00.1",
+            ]
+        );
+        assert_eq!(read(&dir, "app.log.2024-05-01T13-00-00"), "1\n");
+        assert_eq!(read(&dir, "app.log.2024-05-01T13-00-00.1"), "2\n");
+    }
+
+    #[test]
+    fn rotates_by_time() {
+        let dir = temp_dir("time");
+        let file = RollingFile::new(dir.join("app.log")).with_rotation(Rotation::Hourly);
+        write(&file, time(13, 10), "at 13:10\n");
+        write(&file, time(13, 50), "at 13:50\n");
+        write(&file, time(14, 5), "at 14:05\n");
+        assert_eq!(files(&dir), ["app.log", "app.log.2024-05-01T13-00-00"]);
+        assert_eq!(
+            read(&dir, "app.log.2024-05-01T13-00-00"),
+            "at 13:10\nat 13:50\n"
+        );
+        assert_eq!(read(&dir, "app.log"), "at 14:05\n");
+    }
+
+    #[test]
+    fn rotates_daily() {
+        let dir = temp_dir("daily");
+        let file = RollingFile::new(dir.join("app.log")).with_rotation(Rotation::Daily);
+        write(&file, time(13, 10), "first day\n");
+        write(&file, time(23, 59), "first day\n");
+        let next_day = Utc.with_ymd_and_hms(2024, 5, 2, 0, 1, 0).unwrap();
+        write(&file, next_day, "second day\n");
+        assert_eq!(files(&dir), ["app.log", "app.log.2024-05-01T00-00-00"]);
+    }
+
+    #[test]
+    fn prunes_to_max_files() {
+        let dir = temp_dir("max-files");
+        let file = RollingFile::new(dir.join("app.log"))
+            .with_max_size(1)
+            .with_max_files(2);
+        for minute in 0..5 {
+            write(&file, time(13, minute), &format!("{minute}\n"));
+        }
+        assert_eq!(
+            files(&dir),
+            [
+                "app.log",
+                "app.log.2024-05-01T13-03-00",
+                "app.log.2024-05-01T13-04-00",
+            ]
+        );
+        assert_eq!(read(&dir, "app.log.2024-05-01T13-03-00"), "2\n");
+        assert_eq!(read(&dir, "app.log"), "4\n");
+    }
+
+    #[test]
+    fn keeps_writing_when_pruning_fails() {
+        let dir = temp_dir("prune-failure");
+        // A directory can't be removed with `remove_file`.
+        fs::create_dir(dir.join("app.log.2024-01-01T00-00-00")).unwrap();
+        let file = RollingFile::new(dir.join("app.log"))
+            .with_max_size(1)
+            .with_max_files(1);
+        write(&file, time(13, 10), "1\n");
+        write(&file, time(13, 20), "2\n");
+        assert_eq!(read(&dir, "app.log.2024-05-01T13-20-00"), "1\n");
+        assert_eq!(read(&dir, "app.log"), "2\n");
+    }
+}
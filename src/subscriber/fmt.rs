@@ -1,20 +1,33 @@
 //! Subscriber that prints to stdout/stderr.
 //!
-//! Supports pretty printing with colors.
+//! Supports pretty printing with colors, newline-delimited JSON, logfmt and
+//! custom [templates](template). Lines can be written to other destinations,
+//! such as a file, with a [writer](writer).
 
+pub mod template;
+pub mod writer;
+
+use std::borrow::Cow;
 use std::fmt::Write;
+use std::io::Write as _;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use yansi::Color;
+use serde_json::json;
+use yansi::{Color, Paint};
 
-use crate::{level::LevelFilter, Event, Level, Metadata};
+use crate::{filter::EnvFilter, level::LevelFilter, Event, Level, Metadata, Value};
 
-use super::Subscriber;
+use self::template::Template;
+use self::writer::{MakeWriter, Writer};
+use super::{Interest, Subscriber};
 
 const GRAY: Color = Color::Black;
 
-/// A subscriber printing to stdout/stderr.
+/// A subscriber printing formatted events.
+///
+/// By default, [`Level::Error`] events are printed to stderr and all other
+/// events to stdout. This can be changed with [`with_writer`](Self::with_writer).
 ///
 /// # Basic example
 ///
@@ -27,16 +40,31 @@ const GRAY: Color = Color::Black;
 /// ```
 /// lunatic_log::init(FmtSubscriber::new(LevelFilter::Info).pretty());
 /// ```
+///
+/// # JSON example
+///
+/// ```
+/// lunatic_log::init(FmtSubscriber::new(LevelFilter::Info).json());
+/// ```
+///
+/// # Logfmt example
+///
+/// ```
+/// lunatic_log::init(FmtSubscriber::new(LevelFilter::Info).logfmt());
+/// ```
 #[derive(Serialize, Deserialize)]
-pub struct FmtSubscriber {
+pub struct FmtSubscriber<W = Writer> {
     color: bool,
     file: bool,
+    filter: EnvFilter,
+    format: Format,
     level: bool,
-    level_filter: LevelFilter,
     line_number: bool,
+    spans: bool,
     target: bool,
     time: bool,
     time_format: Option<String>,
+    writer: W,
 }
 
 impl Default for FmtSubscriber {
@@ -44,12 +72,15 @@ impl Default for FmtSubscriber {
         Self {
             color: false,
             file: false,
+            filter: LevelFilter::Off.into(),
+            format: Format::Full,
             level: false,
-            level_filter: LevelFilter::Off,
             line_number: false,
+            spans: false,
             target: false,
             time: false,
             time_format: None,
+            writer: Writer::default(),
         }
     }
 }
@@ -58,22 +89,84 @@ impl FmtSubscriber {
     /// Creates an instance of [`FmtSubscriber`].
     pub fn new(level_filter: LevelFilter) -> Self {
         FmtSubscriber {
-            level_filter,
+            filter: level_filter.into(),
             ..Default::default()
         }
     }
+}
 
+impl<W> FmtSubscriber<W> {
     /// Configures logging to be pretty with colors, filenames, and more.
     pub fn pretty(mut self) -> Self {
         self.color = true;
         self.file = true;
         self.level = true;
         self.line_number = true;
+        self.spans = true;
         self.target = true;
         self.time = true;
         self
     }
 
+    /// Configures logging to print one JSON object per line.
+    ///
+    /// Each object contains the timestamp, level, target, module path, file,
+    /// line and message of the event, along with its fields and spans.
+    pub fn json(mut self) -> Self {
+        self.format = Format::Json;
+        self
+    }
+
+    /// Configures logging to print `key=value` pairs in the logfmt format.
+    ///
+    /// Each line contains the timestamp, level, target, file, line and message
+    /// of the event, followed by its spans and fields. Characters not allowed in
+    /// keys are replaced with `_` in field names, and fields named like a key
+    /// printed for every event, such as `level` or `msg`, are prefixed with
+    /// `field.`.
+    pub fn logfmt(mut self) -> Self {
+        self.format = Format::Logfmt;
+        self
+    }
+
+    /// Configures logging to print lines formatted with a [`Template`].
+    ///
+    /// The other options, such as [`with_time_format`](Self::with_time_format),
+    /// are ignored in favor of the template.
+    pub fn template(mut self, template: Template) -> Self {
+        self.format = Format::Template(template);
+        self
+    }
+
+    /// Sets the destination of the printed lines.
+    pub fn with_writer<W2: MakeWriter>(self, writer: W2) -> FmtSubscriber<W2> {
+        FmtSubscriber {
+            color: self.color,
+            file: self.file,
+            filter: self.filter,
+            format: self.format,
+            level: self.level,
+            line_number: self.line_number,
+            spans: self.spans,
+            target: self.target,
+            time: self.time,
+            time_format: self.time_format,
+            writer,
+        }
+    }
+
+    pub(crate) fn filter(&self) -> &EnvFilter {
+        &self.filter
+    }
+
+    pub(crate) fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub(crate) fn into_writer(self) -> W {
+        self.writer
+    }
+
     /// Enables printing color.
     pub fn with_color(mut self, color: bool) -> Self {
         self.color = color;
@@ -86,6 +179,12 @@ impl FmtSubscriber {
         self
     }
 
+    /// Filter logs with an [`EnvFilter`], replacing the level filter.
+    pub fn with_env_filter(mut self, filter: EnvFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
     /// Print the log level.
     pub fn with_level(mut self, level: bool) -> Self {
         self.level = level;
@@ -98,13 +197,19 @@ impl FmtSubscriber {
         self
     }
 
+    /// Print the spans the log was emitted in.
+    pub fn with_spans(mut self, spans: bool) -> Self {
+        self.spans = spans;
+        self
+    }
+
     /// Print the target of the log.
     pub fn with_target(mut self, target: bool) -> Self {
         self.target = target;
         self
     }
 
-    /// Print the time with the log.
+    /// Print the time the event was emitted with the log.
     pub fn with_time(mut self, time: bool) -> Self {
         self.time = time;
         self
@@ -119,12 +224,18 @@ impl FmtSubscriber {
     }
 }
 
-impl Subscriber for FmtSubscriber {
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= &self.level_filter
+impl<W> FmtSubscriber<W> {
+    fn timestamp(&self, event: &Event) -> String {
+        DateTime::<Utc>::from(event.timestamp())
+            .format(
+                self.time_format
+                    .as_deref()
+                    .unwrap_or("%Y-%m-%dT%H:%M:%S%.6fZ"),
+            )
+            .to_string()
     }
 
-    fn event(&self, event: &Event) {
+    fn format_full(&self, event: &Event) -> String {
         let mut line = String::new();
         macro_rules! insert_space {
             () => {
@@ -136,14 +247,7 @@ impl Subscriber for FmtSubscriber {
 
         if self.time {
             insert_space!();
-            let now = Utc::now();
-            let now_string = now
-                .format(
-                    self.time_format
-                        .as_deref()
-                        .unwrap_or("%Y-%m-%dT%H:%M:%S%.6fZ"),
-                )
-                .to_string();
+            let now_string = self.timestamp(event);
             if self.color {
                 write!(line, "{}", GRAY.paint(now_string)).unwrap();
             } else {
@@ -213,12 +317,276 @@ impl Subscriber for FmtSubscriber {
             }
         }
 
+        if self.spans && !event.spans().is_empty() {
+            insert_space!();
+            for span in event.spans() {
+                let name = span.metadata().name();
+                if self.color {
+                    write!(line, "{}", Paint::new(name).bold()).unwrap();
+                } else {
+                    line.push_str(name);
+                }
+                let mut fields = span.fields().peekable();
+                if fields.peek().is_some() {
+                    line.push('{');
+                    for (i, (name, value)) in fields.enumerate() {
+                        if i > 0 {
+                            line.push(' ');
+                        }
+                        write!(line, "{name}={value}").unwrap();
+                    }
+                    line.push('}');
+                }
+                line.push(':');
+            }
+        }
+
         insert_space!();
+        line.push_str(event.message());
+
+        for (name, value) in event.context().iter().chain(event.fields()) {
+            insert_space!();
+            if self.color {
+                write!(line, "{}{value}", GRAY.paint(format!("{name}="))).unwrap();
+            } else {
+                write!(line, "{name}={value}").unwrap();
+            }
+        }
+
+        line
+    }
 
-        if event.metadata().level() == &Level::Error {
-            eprintln!("{line}{}", event.message());
-        } else {
-            println!("{line}{}", event.message());
+    fn format_json(&self, event: &Event) -> String {
+        let metadata = event.metadata();
+        let mut object = json!({
+            "timestamp": self.timestamp(event),
+            "level": metadata.level().as_str(),
+            "target": metadata.target(),
+            "module_path": metadata.module_path(),
+            "file": metadata.file(),
+            "line": metadata.line(),
+            "message": event.message(),
+        });
+        let fields = json_fields(event);
+        if !fields.is_empty() {
+            object["fields"] = fields.into();
         }
+        if !event.spans().is_empty() {
+            object["spans"] = json_spans(event).into();
+        }
+        object.to_string()
+    }
+
+    fn format_logfmt(&self, event: &Event) -> String {
+        let metadata = event.metadata();
+        let mut line = String::new();
+        let mut pair = |key: &str, value: &str| {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(key);
+            line.push('=');
+            push_logfmt_value(&mut line, value);
+        };
+
+        pair("ts", &self.timestamp(event));
+        pair("level", &metadata.level().as_str().to_lowercase());
+        pair("target", metadata.target());
+        if let Some(file) = metadata.file() {
+            pair("file", file);
+        }
+        if let Some(line_number) = metadata.line() {
+            pair("line", &line_number.to_string());
+        }
+        pair("msg", event.message());
+        if !event.spans().is_empty() {
+            let names: Vec<_> = event
+                .spans()
+                .iter()
+                .map(|span| span.metadata().name().as_str())
+                .collect();
+            pair("span", &names.join(":"));
+        }
+        let span_fields = event.spans().iter().flat_map(|span| span.fields());
+        for (name, value) in span_fields.chain(event.context()).chain(event.fields()) {
+            pair(&logfmt_key(name), &value.to_string());
+        }
+        line
+    }
+}
+
+/// The keys printed for every event in the logfmt format.
+const LOGFMT_KEYS: [&str; 7] = ["ts", "level", "target", "file", "line", "msg", "span"];
+
+/// Returns the logfmt key of a field, replacing characters not allowed in keys
+/// with `_`, and prefixing names clashing with the keys printed for every
+/// event with `field.`.
+pub(crate) fn logfmt_key(name: &str) -> Cow<'_, str> {
+    let invalid = |c: char| c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control();
+    let key = if name.is_empty() {
+        Cow::Borrowed("_")
+    } else if name.contains(invalid) {
+        Cow::Owned(name.replace(invalid, "_"))
+    } else {
+        Cow::Borrowed(name)
+    };
+    if LOGFMT_KEYS.contains(&key.as_ref()) {
+        Cow::Owned(format!("field.{key}"))
+    } else {
+        key
+    }
+}
+
+/// Pushes a logfmt value, quoting and escaping it if needed.
+pub(crate) fn push_logfmt_value(line: &mut String, value: &str) {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control());
+    if !needs_quotes {
+        line.push_str(value);
+        return;
+    }
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if c.is_control() => write!(line, "\\u{:04x}", c as u32).unwrap(),
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+/// Returns the context and fields of an event as a JSON object.
+pub(crate) fn json_fields(event: &Event) -> serde_json::Map<String, serde_json::Value> {
+    event
+        .context()
+        .iter()
+        .chain(event.fields())
+        .map(|(name, value)| (name.clone(), json_value(value)))
+        .collect()
+}
+
+/// Returns the spans of an event as JSON objects holding their fields and
+/// `name`, from the outermost to the innermost.
+pub(crate) fn json_spans(event: &Event) -> Vec<serde_json::Value> {
+    event
+        .spans()
+        .iter()
+        .map(|span| {
+            let mut object: serde_json::Map<_, _> = span
+                .fields()
+                .map(|(name, value)| (name.clone(), json_value(value)))
+                .collect();
+            object.insert("name".to_string(), span.metadata().name().clone().into());
+            object.into()
+        })
+        .collect()
+}
+
+pub(crate) fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Str(s) | Value::Debug(s) | Value::Display(s) => s.clone().into(),
+        Value::I64(n) => (*n).into(),
+        Value::U64(n) => (*n).into(),
+        Value::F64(n) => (*n).into(),
+        Value::Bool(b) => (*b).into(),
+    }
+}
+
+/// The format of the lines printed by a [`FmtSubscriber`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Format {
+    Full,
+    Json,
+    Logfmt,
+    Template(Template),
+}
+
+impl<W: MakeWriter> Subscriber for FmtSubscriber<W> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn event(&self, event: &Event) {
+        let mut line = match &self.format {
+            Format::Full => self.format_full(event),
+            Format::Json => self.format_json(event),
+            Format::Logfmt => self.format_logfmt(event),
+            Format::Template(template) => template.render(event, &event.timestamp().into()),
+        };
+
+        line.push('\n');
+        let mut writer = self.writer.make_writer(event.metadata());
+        let _ = writer.write_all(line.as_bytes());
+    }
+
+    fn interest(&self) -> Interest {
+        self.filter.interest()
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.filter = filter;
+    }
+
+    fn flush(&self) {
+        self.writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logfmt_value(value: &str) -> String {
+        let mut line = String::new();
+        push_logfmt_value(&mut line, value);
+        line
+    }
+
+    #[test]
+    fn logfmt_values() {
+        assert_eq!(logfmt_value("alice"), "alice");
+        assert_eq!(logfmt_value("2.5"), "2.5");
+        assert_eq!(logfmt_value(""), r#""""#);
+    }
+
+    #[test]
+    fn logfmt_values_quoted() {
+        assert_eq!(logfmt_value("two words"), r#""two words""#);
+        assert_eq!(logfmt_value("a=b"), r#""a=b""#);
+        assert_eq!(logfmt_value(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(logfmt_value(r"C:\temp"), r#""C:\\temp""#);
+    }
+
+    #[test]
+    fn logfmt_values_escaped() {
+        assert_eq!(logfmt_value("a\nb\tc"), r#""a\nb\tc""#);
+        assert_eq!(logfmt_value("\u{1b}[0m"), r#""\u001b[0m""#);
+        assert_eq!(logfmt_value("\u{7f}"), r#""\u007f""#);
+    }
+
+    #[test]
+    fn logfmt_keys() {
+        assert_eq!(logfmt_key("user"), "user");
+        assert_eq!(logfmt_key("user.id"), "user.id");
+        assert_eq!(logfmt_key("user id"), "user_id");
+        assert_eq!(logfmt_key("a=b"), "a_b");
+        assert_eq!(logfmt_key("say\"hi\\"), "say_hi_");
+        assert_eq!(logfmt_key("new\nline"), "new_line");
+        assert_eq!(logfmt_key(""), "_");
+    }
+
+    #[test]
+    fn logfmt_keys_clashing() {
+        assert_eq!(logfmt_key("msg"), "field.msg");
+        assert_eq!(logfmt_key("level"), "field.level");
+        assert_eq!(logfmt_key("ts"), "field.ts");
+        assert_eq!(logfmt_key("message"), "message");
     }
 }
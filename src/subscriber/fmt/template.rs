@@ -0,0 +1,392 @@
+//! Custom line formats for [`FmtSubscriber`](super::FmtSubscriber).
+//!
+//! # Syntax
+//!
+//! A template is text with components in braces, such as `{level}`. Literal
+//! braces are written as `{{` and `}}`. The supported components are:
+//!
+//! - `time`, the time the event was emitted, optionally with a `strftime` format as in
+//!   `{time:%H:%M:%S}`
+//! - `level`, `name`, `target`, `module_path`, `file` and `line` from the
+//!   event's [`Metadata`](crate::Metadata)
+//! - `message`, the message of the event
+//! - `spans`, the spans the event was emitted in, as `name{key=value}:`
+//! - `fields`, the context and fields of the event, as `key=value`
+//!
+//! Every component except `time` accepts a format spec after a colon: an
+//! optional alignment (`<`, `^` or `>`), an optional minimum width and an
+//! optional maximum width after a dot. For example `{level:>5}` right-aligns
+//! the level and `{target:.20}` truncates the target to 20 characters.
+//! Missing components, such as the file of an event without one, are empty.
+
+use std::{error, fmt, fmt::Write, str::FromStr};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Utc,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::Event;
+
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
+
+/// A line format, parsed once from a template string.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{subscriber::fmt::{template::Template, FmtSubscriber}, LevelFilter};
+///
+/// let template: Template = "{time:%H:%M:%S} [{level:>5}] {target}: {message}".parse().unwrap();
+/// lunatic_log::init(FmtSubscriber::new(LevelFilter::Info).template(template));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Part {
+    Literal(String),
+    Time(Option<String>),
+    Component(Component, Spec),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Component {
+    Level,
+    Name,
+    Target,
+    ModulePath,
+    File,
+    Line,
+    Message,
+    Spans,
+    Fields,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Spec {
+    align: Align,
+    min_width: Option<usize>,
+    max_width: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Template {
+    /// Parses a template string.
+    pub fn new(template: &str) -> Result<Self, ParseTemplateError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| ParseTemplateError::new("unclosed `{`"))?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::parse(&rest[..end])?);
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(ParseTemplateError::new("unmatched `}`")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template {
+            source: template.to_string(),
+            parts,
+        })
+    }
+
+    /// Returns the template string this was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Renders an [`Event`] with the template, with `time` as the time it was
+    /// emitted, usually [`Event::timestamp`].
+    pub fn render(&self, event: &Event, time: &DateTime<Utc>) -> String {
+        let metadata = event.metadata();
+        let mut line = String::new();
+        for part in &self.parts {
+            let (component, spec) = match part {
+                Part::Literal(literal) => {
+                    line.push_str(literal);
+                    continue;
+                }
+                Part::Time(format) => {
+                    let format = format.as_deref().unwrap_or(DEFAULT_TIME_FORMAT);
+                    write!(line, "{}", time.format(format)).unwrap();
+                    continue;
+                }
+                Part::Component(component, spec) => (component, spec),
+            };
+            let value = match component {
+                Component::Level => metadata.level().as_str().to_string(),
+                Component::Name => metadata.name().clone(),
+                Component::Target => metadata.target().clone(),
+                Component::ModulePath => metadata.module_path().cloned().unwrap_or_default(),
+                Component::File => metadata.file().cloned().unwrap_or_default(),
+                Component::Line => metadata.line().map(|l| l.to_string()).unwrap_or_default(),
+                Component::Message => event.message().clone(),
+                Component::Spans => {
+                    let mut spans = String::new();
+                    for span in event.spans() {
+                        spans.push_str(span.metadata().name());
+                        let mut fields = span.fields().peekable();
+                        if fields.peek().is_some() {
+                            spans.push('{');
+                            for (i, (name, value)) in fields.enumerate() {
+                                if i > 0 {
+                                    spans.push(' ');
+                                }
+                                write!(spans, "{name}={value}").unwrap();
+                            }
+                            spans.push('}');
+                        }
+                        spans.push(':');
+                    }
+                    spans
+                }
+                Component::Fields => {
+                    let mut fields = String::new();
+                    for (name, value) in event.context().iter().chain(event.fields()) {
+                        if !fields.is_empty() {
+                            fields.push(' ');
+                        }
+                        write!(fields, "{name}={value}").unwrap();
+                    }
+                    fields
+                }
+            };
+            spec.push(&mut line, &value);
+        }
+        line
+    }
+}
+
+impl Part {
+    fn parse(part: &str) -> Result<Self, ParseTemplateError> {
+        let (name, spec) = match part.split_once(':') {
+            Some((name, spec)) => (name.trim(), Some(spec)),
+            None => (part.trim(), None),
+        };
+        let component = match name {
+            "time" => {
+                if let Some(format) = spec {
+                    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                        return Err(ParseTemplateError::new(format!(
+                            "invalid time format `{format}`"
+                        )));
+                    }
+                }
+                return Ok(Part::Time(spec.map(str::to_string)));
+            }
+            "level" => Component::Level,
+            "name" => Component::Name,
+            "target" => Component::Target,
+            "module_path" => Component::ModulePath,
+            "file" => Component::File,
+            "line" => Component::Line,
+            "message" => Component::Message,
+            "spans" => Component::Spans,
+            "fields" => Component::Fields,
+            _ => {
+                return Err(ParseTemplateError::new(format!(
+                    "unknown component `{name}`"
+                )))
+            }
+        };
+        let spec = match spec {
+            Some(spec) => spec.parse()?,
+            None => Spec::default(),
+        };
+        Ok(Part::Component(component, spec))
+    }
+}
+
+impl Spec {
+    fn push(&self, line: &mut String, value: &str) {
+        let value: String = match self.max_width {
+            Some(max_width) => value.chars().take(max_width).collect(),
+            None => value.to_string(),
+        };
+        let padding = self
+            .min_width
+            .unwrap_or(0)
+            .saturating_sub(value.chars().count());
+        let (left, right) = match self.align {
+            Align::Left => (0, padding),
+            Align::Center => (padding / 2, padding - padding / 2),
+            Align::Right => (padding, 0),
+        };
+        write!(line, "{:left$}{value}{:right$}", "", "").unwrap();
+    }
+}
+
+impl FromStr for Spec {
+    type Err = ParseTemplateError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let error = || ParseTemplateError::new(format!("invalid format spec `{spec}`"));
+        let (align, rest) = match spec.chars().next() {
+            Some('<') => (Align::Left, &spec[1..]),
+            Some('^') => (Align::Center, &spec[1..]),
+            Some('>') => (Align::Right, &spec[1..]),
+            _ => (Align::Left, spec),
+        };
+        let (min_width, max_width) = match rest.split_once('.') {
+            Some((min_width, max_width)) => (min_width, Some(max_width)),
+            None => (rest, None),
+        };
+        let min_width = match min_width {
+            "" => None,
+            width => Some(width.parse().map_err(|_| error())?),
+        };
+        let max_width = match max_width {
+            Some(width) => Some(width.parse().map_err(|_| error())?),
+            None => None,
+        };
+        Ok(Spec {
+            align,
+            min_width,
+            max_width,
+        })
+    }
+}
+
+impl FromStr for Template {
+    type Err = ParseTemplateError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        Template::new(template)
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// The type returned when a template can't be parsed.
+#[derive(Debug, PartialEq)]
+pub struct ParseTemplateError(String);
+
+impl ParseTemplateError {
+    fn new(reason: impl Into<String>) -> Self {
+        ParseTemplateError(reason.into())
+    }
+}
+
+impl fmt::Display for ParseTemplateError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid template: {}", self.0)
+    }
+}
+
+impl error::Error for ParseTemplateError {}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{Level, Metadata};
+
+    fn render(template: &str) -> String {
+        let metadata = Metadata::new(
+            "event".to_string(),
+            "my_app".to_string(),
+            Level::Info,
+            vec!["user".to_string()],
+            None,
+            None,
+            Some(42),
+        );
+        let event = Event::new("Hello".to_string(), metadata, vec!["alice".into()]);
+        let now = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        Template::new(template).unwrap().render(&event, &now)
+    }
+
+    fn error(template: &str) -> String {
+        Template::new(template).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn renders_components() {
+        assert_eq!(
+            render("[{level}] {target}:{line}: {message} {fields}"),
+            "[INFO] my_app:42: Hello user=alice"
+        );
+        assert_eq!(render("{file}|{module_path}|{spans}"), "||");
+    }
+
+    #[test]
+    fn escapes_braces() {
+        assert_eq!(render("{{{level}}}"), "{INFO}");
+        assert_eq!(render("{{level}}"), "{level}");
+    }
+
+    #[test]
+    fn aligns_and_truncates() {
+        assert_eq!(render("{level:>6}|"), "  INFO|");
+        assert_eq!(render("{level:<6}|"), "INFO  |");
+        assert_eq!(render("{level:^8}|"), "  INFO  |");
+        assert_eq!(render("{level:6}|"), "INFO  |");
+        assert_eq!(render("{target:.2}|"), "my|");
+        assert_eq!(render("{target:>4.2}|"), "  my|");
+    }
+
+    #[test]
+    fn formats_time() {
+        assert_eq!(render("{time}"), "2024-01-02T03:04:05.000000Z");
+        assert_eq!(render("{time:%H:%M:%S}"), "03:04:05");
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert_eq!(error("{level"), "invalid template: unclosed `{`");
+        assert_eq!(error("level}"), "invalid template: unmatched `}`");
+        assert_eq!(
+            error("{host}"),
+            "invalid template: unknown component `host`"
+        );
+        assert_eq!(
+            error("{level:x}"),
+            "invalid template: invalid format spec `x`"
+        );
+        assert_eq!(
+            error("{level:>.}"),
+            "invalid template: invalid format spec `>.`"
+        );
+        assert_eq!(
+            error("{time:%Q}"),
+            "invalid template: invalid time format `%Q`"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Destinations for the lines printed by [`FmtSubscriber`](super::FmtSubscriber).
+//!
+//! A writer is configured in the process calling [`init`](crate::init), and
+//! sent to the subscriber process, where the underlying output is opened. This
+//! is why writers are described by a serializable [`MakeWriter`], instead of an
+//! [`io::Write`] implementation.
+
+use std::{
+    cell::OnceCell,
+    fs::{File, OpenOptions},
+    io,
+    path::PathBuf,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Level, Metadata};
+
+/// Creates the [`io::Write`] a formatted event is written to.
+///
+/// This is implemented by [`Writer`], and can be implemented for custom sinks.
+/// Any state, such as an open connection, should be skipped when serializing
+/// and created lazily inside the subscriber process.
+///
+/// # Example
+///
+/// ```
+/// use std::{cell::RefCell, io};
+///
+/// use lunatic_log::{subscriber::fmt::writer::MakeWriter, Metadata};
+/// use serde::{Deserialize, Serialize};
+///
+/// /// Keeps formatted events in memory.
+/// #[derive(Default, Serialize, Deserialize)]
+/// pub struct InMemory {
+///     #[serde(skip)]
+///     buffer: RefCell<Vec<u8>>,
+/// }
+///
+/// impl MakeWriter for InMemory {
+///     fn make_writer(&self, _metadata: &Metadata) -> Box<dyn io::Write + '_> {
+///         Box::new(Buffer(&self.buffer))
+///     }
+/// }
+///
+/// struct Buffer<'a>(&'a RefCell<Vec<u8>>);
+///
+/// impl io::Write for Buffer<'_> {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         self.0.borrow_mut().write(buf)
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait MakeWriter: Serialize + DeserializeOwned {
+    /// Returns the writer for an event with the given [`Metadata`].
+    fn make_writer(&self, metadata: &Metadata) -> Box<dyn io::Write + '_>;
+
+    /// Flush any buffered output.
+    fn flush(&self) {}
+}
+
+/// The built-in destinations of a [`FmtSubscriber`](super::FmtSubscriber).
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{
+///     subscriber::fmt::{writer::Writer, FmtSubscriber},
+///     LevelFilter,
+/// };
+///
+/// lunatic_log::init(FmtSubscriber::new(LevelFilter::Info).with_writer(Writer::file("app.log")));
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Writer {
+    output: Output,
+    #[serde(skip)]
+    file: OnceCell<Option<File>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum Output {
+    Stdout,
+    Stderr,
+    Split(Level),
+    File(PathBuf),
+}
+
+impl Default for Writer {
+    /// Writes [`Level::Error`] events to stderr, and all other events to stdout.
+    fn default() -> Self {
+        Writer::split(Level::Error)
+    }
+}
+
+impl Writer {
+    fn new(output: Output) -> Self {
+        Writer {
+            output,
+            file: OnceCell::new(),
+        }
+    }
+
+    /// Writes all events to stdout.
+    pub fn stdout() -> Self {
+        Writer::new(Output::Stdout)
+    }
+
+    /// Writes all events to stderr.
+    pub fn stderr() -> Self {
+        Writer::new(Output::Stderr)
+    }
+
+    /// Writes events at `threshold` or a more severe level to stderr, and all
+    /// other events to stdout.
+    pub fn split(threshold: Level) -> Self {
+        Writer::new(Output::Split(threshold))
+    }
+
+    /// Appends all events to a file, which is created if it doesn't exist.
+    ///
+    /// The file is opened by the subscriber process. If it can't be opened, an
+    /// error is printed to stderr and events are discarded.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Writer::new(Output::File(path.into()))
+    }
+
+    fn open_file(&self, path: &PathBuf) -> Option<&File> {
+        self.file
+            .get_or_init(
+                || match OpenOptions::new().create(true).append(true).open(path) {
+                    Ok(file) => Some(file),
+                    Err(err) => {
+                        eprintln!("lunatic-log: failed to open {}: {err}", path.display());
+                        None
+                    }
+                },
+            )
+            .as_ref()
+    }
+}
+
+impl MakeWriter for Writer {
+    fn make_writer(&self, metadata: &Metadata) -> Box<dyn io::Write + '_> {
+        match &self.output {
+            Output::Stdout => Box::new(io::stdout()),
+            Output::Stderr => Box::new(io::stderr()),
+            Output::Split(threshold) if metadata.level() <= threshold => Box::new(io::stderr()),
+            Output::Split(_) => Box::new(io::stdout()),
+            Output::File(path) => match self.open_file(path) {
+                Some(file) => Box::new(file),
+                None => Box::new(io::sink()),
+            },
+        }
+    }
+
+    fn flush(&self) {
+        match &self.output {
+            Output::Stdout => {
+                let _ = io::Write::flush(&mut io::stdout());
+            }
+            Output::Stderr => {
+                let _ = io::Write::flush(&mut io::stderr());
+            }
+            Output::Split(_) => {
+                let _ = io::Write::flush(&mut io::stdout());
+                let _ = io::Write::flush(&mut io::stderr());
+            }
+            Output::File(_) => {
+                if let Some(Some(file)) = self.file.get() {
+                    let _ = io::Write::flush(&mut &*file);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Subscriber that sends events to Graylog.
+//!
+//! Events are encoded as [GELF 1.1] JSON and sent over UDP, split into chunks
+//! if they don't fit into a single datagram, or over TCP, delimited by null
+//! bytes.
+//!
+//! [GELF 1.1]: https://go2docs.graylog.org/current/getting_in_log_data/gelf.html
+
+use std::{cell::Cell, env};
+
+use chrono::{DateTime, Utc};
+use lunatic::Process;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{filter::EnvFilter, Event, LevelFilter, Metadata};
+
+use super::{
+    fmt::json_value,
+    syslog::severity,
+    transport::{Connection, Protocol},
+    Interest, Subscriber,
+};
+
+/// The default size of UDP datagrams, which fits into the MTU of most networks.
+const DEFAULT_CHUNK_SIZE: usize = 1420;
+/// The size of the header starting every chunk.
+const CHUNK_HEADER_SIZE: usize = 12;
+/// The maximum number of chunks of a message accepted by Graylog.
+const MAX_CHUNKS: usize = 128;
+
+/// A subscriber sending events to Graylog in the GELF format.
+///
+/// Every event is sent with its message as `short_message`, its level as a
+/// syslog severity, and its target, file, line, spans and fields as
+/// additional fields.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{subscriber::gelf::GelfSubscriber, LevelFilter};
+///
+/// lunatic_log::init(GelfSubscriber::udp("127.0.0.1:12201", LevelFilter::Info).with_host("my-app"));
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct GelfSubscriber {
+    filter: EnvFilter,
+    host: String,
+    chunk_size: usize,
+    connection: Connection,
+    #[serde(skip)]
+    message_count: Cell<u32>,
+}
+
+impl GelfSubscriber {
+    fn new(addr: String, protocol: Protocol, level_filter: LevelFilter) -> Self {
+        GelfSubscriber {
+            filter: level_filter.into(),
+            host: env::var("HOSTNAME").unwrap_or_else(|_| "lunatic".to_string()),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            connection: Connection::new(addr, protocol),
+            message_count: Cell::new(0),
+        }
+    }
+
+    /// Creates an instance of [`GelfSubscriber`] sending to `addr` over UDP.
+    pub fn udp(addr: impl Into<String>, level_filter: LevelFilter) -> Self {
+        GelfSubscriber::new(addr.into(), Protocol::Udp, level_filter)
+    }
+
+    /// Creates an instance of [`GelfSubscriber`] sending to `addr` over TCP.
+    pub fn tcp(addr: impl Into<String>, level_filter: LevelFilter) -> Self {
+        GelfSubscriber::new(addr.into(), Protocol::Tcp, level_filter)
+    }
+
+    /// Filter logs with an [`EnvFilter`], replacing the level filter.
+    pub fn with_env_filter(mut self, filter: EnvFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the `host` of the messages.
+    ///
+    /// Defaults to the `HOSTNAME` environment variable, or `lunatic` if it isn't set.
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Sets the maximum size of UDP datagrams, 1420 bytes by default.
+    ///
+    /// Larger messages are split into chunks. Messages needing more than 128
+    /// chunks are discarded.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(CHUNK_HEADER_SIZE + 1);
+        self
+    }
+
+    fn encode(&self, event: &Event) -> String {
+        let metadata = event.metadata();
+        let timestamp = DateTime::<Utc>::from(event.timestamp());
+        let mut message = json!({
+            "version": "1.1",
+            "host": self.host,
+            "short_message": event.message(),
+            "timestamp": timestamp.timestamp_micros() as f64 / 1_000_000.0,
+            "level": severity(metadata.level()),
+            "_target": metadata.target(),
+        });
+        let object = message.as_object_mut().unwrap();
+        if let Some(file) = metadata.file() {
+            object.insert("_file".to_string(), file.clone().into());
+        }
+        if let Some(line) = metadata.line() {
+            object.insert("_line".to_string(), line.into());
+        }
+        if !event.spans().is_empty() {
+            let names: Vec<_> = event
+                .spans()
+                .iter()
+                .map(|span| span.metadata().name().as_str())
+                .collect();
+            object.insert("_spans".to_string(), names.join(":").into());
+        }
+        let span_fields = event.spans().iter().flat_map(|span| span.fields());
+        for (name, value) in span_fields.chain(event.context()).chain(event.fields()) {
+            object.insert(field_name(name), json_value(value));
+        }
+        message.to_string()
+    }
+
+    /// Sends a message over UDP, split into chunks if needed.
+    fn send_chunked(&self, message: &[u8]) {
+        if message.len() <= self.chunk_size {
+            self.connection.send(message);
+            return;
+        }
+
+        match chunks(message, self.chunk_size, self.message_id()) {
+            Some(chunks) => {
+                for chunk in chunks {
+                    self.connection.send(&chunk);
+                }
+            }
+            None => eprintln!(
+                "lunatic-log: discarding GELF message of {} bytes, which needs more than {MAX_CHUNKS} chunks",
+                message.len()
+            ),
+        }
+    }
+
+    /// Returns an id for a chunked message, unique to the subscriber process
+    /// and message.
+    fn message_id(&self) -> [u8; 8] {
+        let count = self.message_count.get();
+        self.message_count.set(count.wrapping_add(1));
+        let process_id = Process::<()>::this().id() as u32;
+        let nanos = Utc::now().timestamp_subsec_nanos();
+        let id = ((process_id ^ nanos) as u64) << 32 | count as u64;
+        id.to_be_bytes()
+    }
+}
+
+impl Subscriber for GelfSubscriber {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn event(&self, event: &Event) {
+        let message = self.encode(event);
+        match self.connection.protocol() {
+            Protocol::Udp => self.send_chunked(message.as_bytes()),
+            Protocol::Tcp => {
+                let mut frame = message.into_bytes();
+                frame.push(0);
+                self.connection.send(&frame);
+            }
+        }
+    }
+
+    fn interest(&self) -> Interest {
+        self.filter.interest()
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.filter = filter;
+    }
+
+    fn flush(&self) {
+        self.connection.flush();
+    }
+}
+
+/// Splits a message into chunks of at most `chunk_size` bytes, each starting
+/// with the chunk header, or returns `None` if it needs more than 128 chunks.
+fn chunks(message: &[u8], chunk_size: usize, id: [u8; 8]) -> Option<Vec<Vec<u8>>> {
+    let payload_size = chunk_size - CHUNK_HEADER_SIZE;
+    let count = message.len().div_ceil(payload_size);
+    if count > MAX_CHUNKS {
+        return None;
+    }
+    let chunks = message
+        .chunks(payload_size)
+        .enumerate()
+        .map(|(sequence, payload)| {
+            let mut chunk = Vec::with_capacity(CHUNK_HEADER_SIZE + payload.len());
+            chunk.extend_from_slice(&[0x1e, 0x0f]);
+            chunk.extend_from_slice(&id);
+            chunk.push(sequence as u8);
+            chunk.push(count as u8);
+            chunk.extend_from_slice(payload);
+            chunk
+        })
+        .collect();
+    Some(chunks)
+}
+
+/// Returns the name of an additional field, which may only contain word
+/// characters, dots and dashes, and must not be `_id`.
+fn field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') => c,
+            _ => '_',
+        })
+        .collect();
+    match name.as_str() {
+        "id" => "_id_".to_string(),
+        _ => format!("_{name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lunatic::net::UdpSocket;
+
+    use super::*;
+
+    const ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    #[test]
+    fn chunk_headers() {
+        let message: Vec<u8> = (0..21).collect();
+        let chunks = chunks(&message, 20, ID).unwrap();
+        assert_eq!(chunks.len(), 3);
+        for (sequence, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk[..2], [0x1e, 0x0f]);
+            assert_eq!(chunk[2..10], ID);
+            assert_eq!(chunk[10], sequence as u8);
+            assert_eq!(chunk[11], 3);
+        }
+    }
+
+    #[test]
+    fn chunk_payloads() {
+        let message: Vec<u8> = (0..21).collect();
+        let chunks = chunks(&message, 20, ID).unwrap();
+        let sizes: Vec<_> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [20, 20, 17]);
+        let payload: Vec<u8> = chunks
+            .iter()
+            .flat_map(|chunk| chunk[CHUNK_HEADER_SIZE..].iter().copied())
+            .collect();
+        assert_eq!(payload, message);
+    }
+
+    #[test]
+    fn chunks_up_to_128() {
+        // 8 bytes of payload per chunk.
+        let message = vec![b'x'; 128 * 8];
+        let chunks = chunks(&message, 20, ID).unwrap();
+        assert_eq!(chunks.len(), 128);
+        assert_eq!(chunks[127][10..12], [127, 128]);
+    }
+
+    #[test]
+    fn drops_messages_needing_more_than_128_chunks() {
+        let message = vec![b'x'; 128 * 8 + 1];
+        assert_eq!(chunks(&message, 20, ID), None);
+    }
+
+    #[test]
+    fn message_ids_differ() {
+        let subscriber = GelfSubscriber::udp("127.0.0.1:12201", LevelFilter::Info);
+        assert_ne!(subscriber.message_id(), subscriber.message_id());
+    }
+
+    #[test]
+    fn sends_chunked_udp_datagrams() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        let subscriber = GelfSubscriber::udp(addr, LevelFilter::Info).with_chunk_size(20);
+        let mut buf = [0; 64];
+
+        subscriber.send_chunked(b"short");
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"short");
+
+        let message: Vec<u8> = (0..21).collect();
+        subscriber.send_chunked(&message);
+        let mut payload = Vec::new();
+        for sequence in 0..3 {
+            let len = socket.recv(&mut buf).unwrap();
+            assert_eq!(buf[..2], [0x1e, 0x0f]);
+            assert_eq!(buf[10..12], [sequence, 3]);
+            payload.extend_from_slice(&buf[CHUNK_HEADER_SIZE..len]);
+        }
+        assert_eq!(payload, message);
+    }
+}
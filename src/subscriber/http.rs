@@ -0,0 +1,198 @@
+//! A minimal HTTP/1.1 client, shared by the subscribers pushing to HTTP APIs.
+
+use std::{
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use lunatic::net::TcpStream;
+use serde::{Deserialize, Serialize};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An HTTP endpoint that requests are sent to.
+///
+/// Only plain `http://` URLs are supported. A new connection is opened for
+/// every request, which is cheap compared to the batches sent over it.
+/// Connecting times out after 10 seconds, and sending the request or reading
+/// the response after 30 seconds without progress.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct HttpClient {
+    addr: String,
+    host: String,
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+/// The status and body of an HTTP response.
+#[derive(Debug)]
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Response {
+    pub(crate) fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+impl HttpClient {
+    /// Creates a client for a URL such as `http://localhost:4318/v1/logs`.
+    ///
+    /// The port defaults to 80 and the path to `default_path`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the URL has a scheme other than `http://`, such as `https://`.
+    pub(crate) fn new(url: &str, default_path: &str) -> Self {
+        let url = match url.split_once("://") {
+            Some(("http", url)) => url,
+            Some(_) => panic!("unsupported URL `{url}`, only http:// URLs are supported"),
+            None => url,
+        };
+        let (host, path) = match url.find('/') {
+            Some(index) if index + 1 < url.len() => (&url[..index], &url[index..]),
+            Some(index) => (&url[..index], default_path),
+            None => (url, default_path),
+        };
+        let addr = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{host}:80")
+        };
+        HttpClient {
+            addr,
+            host: host.to_string(),
+            path: path.to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Returns the address and path requests are sent to.
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}{}", self.host, self.path)
+    }
+
+    /// Adds a header sent with every request, such as `Authorization`.
+    pub(crate) fn insert_header(&mut self, name: String, value: String) {
+        self.headers.push((name, value));
+    }
+
+    /// Sends a `POST` request and waits for the response.
+    pub(crate) fn post(&self, content_type: &str, body: &[u8]) -> io::Result<Response> {
+        let mut stream = TcpStream::connect_timeout(self.addr.as_str(), CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let mut head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.path,
+            self.host,
+            body.len()
+        );
+        for (name, value) in &self.headers {
+            head.push_str(name);
+            head.push_str(": ");
+            head.push_str(value);
+            head.push_str("\r\n");
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        // Read until the response is complete, in case the server keeps the
+        // connection open, or until the server closes the connection.
+        let mut response = Vec::new();
+        let mut buf = [0; 8192];
+        loop {
+            let read = stream.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            response.extend_from_slice(&buf[..read]);
+            if let Some(parsed) = parse_response(&response, false)? {
+                return Ok(parsed);
+            }
+        }
+        match parse_response(&response, true)? {
+            Some(response) => Ok(response),
+            None => Err(invalid()),
+        }
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP response")
+}
+
+/// Parses a response, returning `None` if it is incomplete.
+///
+/// A response without a `Content-Length` or chunked body is only complete
+/// once the server closed the connection, which is indicated by `closed`.
+fn parse_response(response: &[u8], closed: bool) -> io::Result<Option<Response>> {
+    let head_end = match response.windows(4).position(|window| window == b"\r\n\r\n") {
+        Some(head_end) => head_end,
+        None if closed => return Err(invalid()),
+        None => return Ok(None),
+    };
+    let head = std::str::from_utf8(&response[..head_end]).map_err(|_| invalid())?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(invalid)?;
+    let mut chunked = false;
+    let mut content_length = None;
+    for line in lines {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), value.trim()),
+            None => continue,
+        };
+        match name.as_str() {
+            "transfer-encoding" => chunked = value.to_ascii_lowercase().contains("chunked"),
+            "content-length" => content_length = Some(value.parse().map_err(|_| invalid())?),
+            _ => {}
+        }
+    }
+
+    let body = &response[head_end + 4..];
+    let body = if chunked {
+        match decode_chunked(body) {
+            Some(body) => body,
+            None if closed => return Err(invalid()),
+            None => return Ok(None),
+        }
+    } else if let Some(content_length) = content_length {
+        match body.get(..content_length) {
+            Some(body) => body.to_vec(),
+            None if closed => return Err(invalid()),
+            None => return Ok(None),
+        }
+    } else if closed {
+        body.to_vec()
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(Response { status, body }))
+}
+
+/// Decodes a body sent with `Transfer-Encoding: chunked`, returning `None` if
+/// it is incomplete or invalid.
+fn decode_chunked(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|window| window == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size = size.split(';').next()?.trim();
+        let size = usize::from_str_radix(size, 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
@@ -0,0 +1,282 @@
+//! Compose filters and enrichers inside a single subscriber.
+//!
+//! A [`Layer`] observes the events and spans handled by a subscriber process,
+//! and can filter or modify events before they reach the subscriber. Layers
+//! are added with [`Subscriber::with`], and the last layer added sees events
+//! first.
+//!
+//! Unlike [`MultipleSubscribers`](super::multiple::MultipleSubscribers), layers
+//! don't spawn processes, so events are not cloned and sent again for every
+//! layer.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{filter::EnvFilter, Event, LevelFilter, Metadata, SpanContext, SpanId, Value};
+
+use super::{Interest, Subscriber};
+
+/// A composable handler of events and spans, wrapping a [`Subscriber`].
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{subscriber::layer::Layer, Event};
+/// use serde::{Deserialize, Serialize};
+///
+/// /// Records the hostname on every event.
+/// #[derive(Serialize, Deserialize)]
+/// pub struct Hostname(String);
+///
+/// impl Layer for Hostname {
+///     fn on_event(&self, event: &Event) -> Option<Event> {
+///         let mut event = event.clone();
+///         event.record("hostname", &self.0);
+///         Some(event)
+///     }
+/// }
+/// ```
+pub trait Layer: Serialize + DeserializeOwned {
+    /// Indicate whether events and spans with the given [`Metadata`] are passed
+    /// on to the layers and subscriber below this layer.
+    ///
+    /// Defaults to `true`.
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    /// Handle a log [`Event`] before it is passed on.
+    ///
+    /// Returns a modified copy of the event to pass on instead, or `None` to
+    /// pass on the event unchanged, which is the default. Events are only
+    /// cloned by layers modifying them.
+    fn on_event(&self, _event: &Event) -> Option<Event> {
+        None
+    }
+
+    /// Handle a newly created span.
+    fn on_new_span(&self, _span: &SpanContext) {}
+
+    /// Handle a span being entered.
+    fn on_enter(&self, _id: &SpanId) {}
+
+    /// Handle a span being exited.
+    fn on_exit(&self, _id: &SpanId) {}
+
+    /// Handle a span being closed.
+    fn on_close(&self, _id: &SpanId) {}
+
+    /// Describes which events the layer passes on.
+    ///
+    /// It narrows the [`Interest`] of the wrapped subscriber. Defaults to
+    /// [`Interest::always`].
+    fn interest(&self) -> Interest {
+        Interest::always()
+    }
+
+    /// Replace the filter of the layer.
+    ///
+    /// This is called when [`set_filter`](crate::set_filter) is used. Layers
+    /// without a configurable filter ignore it.
+    fn set_filter(&mut self, _filter: EnvFilter) {}
+
+    /// Flush any buffered output.
+    fn flush(&self) {}
+
+    /// Only applies this layer to events and spans enabled by a filter.
+    ///
+    /// Unlike stacking the filter as a layer, events not enabled by the filter
+    /// still reach the layers and subscriber below.
+    fn with_filter<F: Layer>(self, filter: F) -> Filtered<Self, F>
+    where
+        Self: Sized,
+    {
+        Filtered {
+            layer: self,
+            filter,
+        }
+    }
+}
+
+/// A [`Subscriber`] wrapped with a [`Layer`].
+///
+/// This is returned by [`Subscriber::with`].
+#[derive(Serialize, Deserialize)]
+pub struct Layered<L, S> {
+    layer: L,
+    inner: S,
+}
+
+impl<L, S> Layered<L, S> {
+    pub(crate) fn new(layer: L, inner: S) -> Self {
+        Layered { layer, inner }
+    }
+}
+
+impl<L: Layer, S: Subscriber> Subscriber for Layered<L, S> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.layer.enabled(metadata) && self.inner.enabled(metadata)
+    }
+
+    fn event(&self, event: &Event) {
+        let modified = self.layer.on_event(event);
+        let event = modified.as_ref().unwrap_or(event);
+        if self.inner.enabled(event.metadata()) {
+            self.inner.event(event);
+        }
+    }
+
+    fn new_span(&self, span: &SpanContext) {
+        self.layer.on_new_span(span);
+        self.inner.new_span(span);
+    }
+
+    fn enter(&self, id: &SpanId) {
+        self.layer.on_enter(id);
+        self.inner.enter(id);
+    }
+
+    fn exit(&self, id: &SpanId) {
+        self.layer.on_exit(id);
+        self.inner.exit(id);
+    }
+
+    fn close(&self, id: &SpanId) {
+        self.layer.on_close(id);
+        self.inner.close(id);
+    }
+
+    fn interest(&self) -> Interest {
+        self.layer.interest().intersection(&self.inner.interest())
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.layer.set_filter(filter.clone());
+        self.inner.set_filter(filter);
+    }
+
+    fn flush(&self) {
+        self.layer.flush();
+        self.inner.flush();
+    }
+}
+
+/// A [`Layer`] only applied to events and spans enabled by a filter.
+///
+/// This is returned by [`Layer::with_filter`].
+#[derive(Serialize, Deserialize)]
+pub struct Filtered<L, F> {
+    layer: L,
+    filter: F,
+}
+
+impl<L: Layer, F: Layer> Layer for Filtered<L, F> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        !self.filter.enabled(metadata) || self.layer.enabled(metadata)
+    }
+
+    fn on_event(&self, event: &Event) -> Option<Event> {
+        if self.filter.enabled(event.metadata()) {
+            self.layer.on_event(event)
+        } else {
+            None
+        }
+    }
+
+    fn on_new_span(&self, span: &SpanContext) {
+        if self.filter.enabled(span.metadata()) {
+            self.layer.on_new_span(span);
+        }
+    }
+
+    fn on_enter(&self, id: &SpanId) {
+        self.layer.on_enter(id);
+    }
+
+    fn on_exit(&self, id: &SpanId) {
+        self.layer.on_exit(id);
+    }
+
+    fn on_close(&self, id: &SpanId) {
+        self.layer.on_close(id);
+    }
+
+    fn flush(&self) {
+        self.layer.flush();
+    }
+}
+
+/// Only passes on events and spans up to a level of verbosity.
+impl Layer for LevelFilter {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self
+    }
+
+    fn interest(&self) -> Interest {
+        Interest::max_level(*self)
+    }
+
+    /// Passes on events up to the most verbose level enabled by the filter,
+    /// leaving filtering by target to the subscriber below.
+    fn set_filter(&mut self, filter: EnvFilter) {
+        *self = filter.max_level_hint();
+    }
+}
+
+/// Only passes on events and spans enabled by the directives.
+impl Layer for EnvFilter {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        EnvFilter::enabled(self, metadata)
+    }
+
+    fn interest(&self) -> Interest {
+        EnvFilter::interest(self)
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        *self = filter;
+    }
+}
+
+/// A [`Layer`] recording static fields on every event, such as the name of the
+/// service.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::subscriber::{fmt::FmtSubscriber, layer::Fields, Subscriber};
+/// use lunatic_log::LevelFilter;
+///
+/// lunatic_log::init(
+///     FmtSubscriber::new(LevelFilter::Info).with(Fields::new().with("service", "api")),
+/// );
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Fields {
+    fields: Vec<(String, Value)>,
+}
+
+impl Fields {
+    /// Creates an instance of [`Fields`] without any fields.
+    pub fn new() -> Self {
+        Fields::default()
+    }
+
+    /// Adds a field recorded on every event.
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.fields.push((name.into(), value.into()));
+        self
+    }
+}
+
+impl Layer for Fields {
+    fn on_event(&self, event: &Event) -> Option<Event> {
+        if self.fields.is_empty() {
+            return None;
+        }
+        let mut event = event.clone();
+        for (name, value) in &self.fields {
+            event.record(name.clone(), value.clone());
+        }
+        Some(event)
+    }
+}
@@ -0,0 +1,300 @@
+//! Subscriber that pushes events to Grafana Loki.
+//!
+//! Events are grouped into streams by their labels and pushed in batches to
+//! the `/loki/api/v1/push` endpoint as JSON. Failed pushes are retried with
+//! exponential backoff.
+//!
+//! Batches are pushed by a worker process linked to the subscriber process,
+//! so waiting for Loki and between retries blocks neither the processes
+//! emitting events nor the subscriber process.
+//!
+//! A batch is pushed when it holds enough events, when it reaches its maximum
+//! age, or when the subscriber is [flushed](crate::flush).
+
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{filter::EnvFilter, Event, LevelFilter, Metadata};
+
+use super::{
+    batch::{Batcher, Batching, Export, ExportError},
+    fmt::{logfmt_key, push_logfmt_value},
+    http::HttpClient,
+    Interest, Subscriber,
+};
+
+const DEFAULT_MAX_BATCH_SIZE: usize = 1024;
+const DEFAULT_MAX_BATCH_AGE: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// A label added to the streams of events, taken from each event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Label {
+    /// The level of the event, as the `level` label.
+    Level,
+    /// The target of the event, as the `target` label.
+    Target,
+    /// The id of the lunatic node running the subscriber, as the `node` label.
+    Node,
+}
+
+/// A subscriber pushing events to Grafana Loki.
+///
+/// Each event is pushed as a logfmt line holding its message, file, line,
+/// spans and fields. Events are grouped into streams by the static labels set
+/// with [`with_label`](Self::with_label), and the labels taken from each
+/// event, only [`Label::Level`] by default.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{
+///     subscriber::loki::{Label, LokiSubscriber},
+///     LevelFilter,
+/// };
+///
+/// lunatic_log::init(
+///     LokiSubscriber::new("http://127.0.0.1:3100", LevelFilter::Info)
+///         .with_label("app", "my-app")
+///         .with_event_labels([Label::Level, Label::Target]),
+/// );
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct LokiSubscriber {
+    filter: EnvFilter,
+    labels: Vec<(String, String)>,
+    event_labels: Vec<Label>,
+    batcher: Batcher<Push>,
+}
+
+/// Pushes batches of entries to Loki.
+#[derive(Clone, Serialize, Deserialize)]
+struct Push {
+    client: HttpClient,
+}
+
+/// A line to be pushed to the stream with the given labels.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    labels: Vec<(String, String)>,
+    timestamp: String,
+    line: String,
+}
+
+impl LokiSubscriber {
+    /// Creates an instance of [`LokiSubscriber`] pushing to the Loki server at `url`.
+    ///
+    /// The url is an `http://` URL, with the path defaulting to `/loki/api/v1/push`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the URL has another scheme, such as `https://`.
+    pub fn new(url: impl AsRef<str>, level_filter: LevelFilter) -> Self {
+        let push = Push {
+            client: HttpClient::new(url.as_ref(), "/loki/api/v1/push"),
+        };
+        let batching = Batching {
+            max_size: DEFAULT_MAX_BATCH_SIZE,
+            max_age: DEFAULT_MAX_BATCH_AGE,
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+        };
+        LokiSubscriber {
+            filter: level_filter.into(),
+            labels: Vec::new(),
+            event_labels: vec![Label::Level],
+            batcher: Batcher::new(push, batching),
+        }
+    }
+
+    /// Filter logs with an [`EnvFilter`], replacing the level filter.
+    pub fn with_env_filter(mut self, filter: EnvFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Adds a label with the same value for every event, such as `app` or `env`.
+    pub fn with_label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        let value = value.into();
+        match self.labels.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.labels.push((name, value)),
+        }
+        self
+    }
+
+    /// Sets the labels taken from each event, replacing the default [`Label::Level`].
+    ///
+    /// Every distinct combination of label values is a separate stream in
+    /// Loki, so labels with many values, such as targets, should be used with care.
+    pub fn with_event_labels(mut self, labels: impl IntoIterator<Item = Label>) -> Self {
+        self.event_labels = labels.into_iter().collect();
+        self
+    }
+
+    /// Adds a header sent with every request, such as `X-Scope-OrgID` or
+    /// `Authorization`.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let client = &mut self.batcher.exporter_mut().client;
+        client.insert_header(name.into(), value.into());
+        self
+    }
+
+    /// Pushes a batch once it holds this many events, 1024 by default.
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.batcher.batching_mut().max_size = max_batch_size.max(1);
+        self
+    }
+
+    /// Pushes a batch this long after its first event arrived, 1 second by
+    /// default.
+    pub fn with_max_batch_age(mut self, max_batch_age: Duration) -> Self {
+        self.batcher.batching_mut().max_age = max_batch_age;
+        self
+    }
+
+    /// Sets how many times a failed push is retried before the batch is
+    /// discarded, 5 by default.
+    ///
+    /// Pushes are retried when the server can't be reached, or responds with
+    /// `429 Too Many Requests` or a server error.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.batcher.batching_mut().max_retries = max_retries;
+        self
+    }
+
+    /// Sets how long to wait before the first retry, 500 milliseconds by default.
+    ///
+    /// The wait doubles after every retry, up to 30 seconds.
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.batcher.batching_mut().initial_backoff = initial_backoff;
+        self
+    }
+
+    fn entry(&self, event: &Event) -> Entry {
+        let metadata = event.metadata();
+        let mut labels = self.labels.clone();
+        for label in &self.event_labels {
+            let label = match label {
+                Label::Level => ("level", metadata.level().as_str().to_lowercase()),
+                Label::Target => ("target", metadata.target().clone()),
+                Label::Node => ("node", lunatic::distributed::node_id().to_string()),
+            };
+            labels.push((label.0.to_string(), label.1));
+        }
+        labels.sort();
+
+        let mut line = String::new();
+        let mut pair = |key: &str, value: &str| {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(key);
+            line.push('=');
+            push_logfmt_value(&mut line, value);
+        };
+        pair("msg", event.message());
+        if let Some(file) = metadata.file() {
+            pair("file", file);
+        }
+        if let Some(line_number) = metadata.line() {
+            pair("line", &line_number.to_string());
+        }
+        if !event.spans().is_empty() {
+            let names: Vec<_> = event
+                .spans()
+                .iter()
+                .map(|span| span.metadata().name().as_str())
+                .collect();
+            pair("span", &names.join(":"));
+        }
+        let span_fields = event.spans().iter().flat_map(|span| span.fields());
+        for (name, value) in span_fields.chain(event.context()).chain(event.fields()) {
+            pair(&logfmt_key(name), &value.to_string());
+        }
+
+        Entry {
+            labels,
+            timestamp: DateTime::<Utc>::from(event.timestamp())
+                .timestamp_nanos_opt()
+                .unwrap_or_default()
+                .to_string(),
+            line,
+        }
+    }
+}
+
+impl Export for Push {
+    type Item = Entry;
+
+    const FAILURE: &'static str = "failed to push logs to";
+
+    fn url(&self) -> String {
+        self.client.url()
+    }
+
+    fn export(&self, entries: Vec<Entry>) -> Result<(), ExportError<Entry>> {
+        let mut streams: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for entry in &entries {
+            streams
+                .entry(&entry.labels)
+                .or_default()
+                .push([&entry.timestamp, &entry.line]);
+        }
+        let streams: Vec<_> = streams
+            .into_iter()
+            .map(|(labels, values)| {
+                let labels: serde_json::Map<_, _> = labels
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone().into()))
+                    .collect();
+                json!({ "stream": labels, "values": values })
+            })
+            .collect();
+        let body = json!({ "streams": streams }).to_string();
+
+        match self.client.post("application/json", body.as_bytes()) {
+            Ok(response) if response.is_success() => Ok(()),
+            Ok(response) => {
+                let error = format!(
+                    "status {}: {}",
+                    response.status,
+                    String::from_utf8_lossy(&response.body).trim()
+                );
+                if response.status == 429 || response.status >= 500 {
+                    Err(ExportError::retry(error, entries))
+                } else {
+                    Err(ExportError::discard(error))
+                }
+            }
+            Err(err) => Err(ExportError::retry(err.to_string(), entries)),
+        }
+    }
+}
+
+impl Subscriber for LokiSubscriber {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn event(&self, event: &Event) {
+        self.batcher.push(self.entry(event));
+    }
+
+    fn interest(&self) -> Interest {
+        self.filter.interest()
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.filter = filter;
+    }
+
+    fn flush(&self) {
+        self.batcher.flush();
+    }
+}
@@ -3,16 +3,20 @@
 use lunatic::Process;
 use serde::{Deserialize, Serialize};
 
-use crate::{spawn_subscriber, Event, Metadata};
+use crate::{
+    filter::EnvFilter, request, spawn_subscriber, Event, Message, Metadata, SpanContext, SpanId,
+};
 
-use super::Subscriber;
+use super::{Interest, Subscriber};
 
 /// Combines multiple subscribers into a single subscriber.
 ///
 /// Child subscriber processes are spawned, and each one is notified of incoming events.
+/// To filter or enrich events for a single subscriber, use [layers](super::layer) instead.
 #[derive(Default, Serialize, Deserialize)]
 pub struct MultipleSubscribers {
-    subscribers: Vec<Process<Event>>,
+    subscribers: Vec<Process<Message>>,
+    interest: Option<Interest>,
 }
 
 impl MultipleSubscribers {
@@ -23,6 +27,11 @@ impl MultipleSubscribers {
 
     /// Adds a child subscriber which runs in its own process.
     pub fn add_subscriber(mut self, subscriber: impl Subscriber) -> Self {
+        let interest = subscriber.interest();
+        self.interest = Some(match self.interest {
+            Some(current) => current.union(&interest),
+            None => interest,
+        });
         let process = spawn_subscriber(subscriber);
         self.subscribers.push(process);
         self
@@ -36,7 +45,50 @@ impl Subscriber for MultipleSubscribers {
 
     fn event(&self, event: &Event) {
         for subscriber in &self.subscribers {
-            subscriber.send(event.clone());
+            subscriber.send(Message::Event(event.clone()));
+        }
+    }
+
+    fn new_span(&self, span: &SpanContext) {
+        for subscriber in &self.subscribers {
+            subscriber.send(Message::NewSpan(span.clone()));
+        }
+    }
+
+    fn enter(&self, id: &SpanId) {
+        for subscriber in &self.subscribers {
+            subscriber.send(Message::Enter(*id));
+        }
+    }
+
+    fn exit(&self, id: &SpanId) {
+        for subscriber in &self.subscribers {
+            subscriber.send(Message::Exit(*id));
+        }
+    }
+
+    fn close(&self, id: &SpanId) {
+        for subscriber in &self.subscribers {
+            subscriber.send(Message::Close(*id));
+        }
+    }
+
+    fn interest(&self) -> Interest {
+        self.interest.clone().unwrap_or_else(Interest::never)
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.interest = Some(filter.interest());
+        for subscriber in &self.subscribers {
+            request(subscriber, |proc, tag| {
+                Message::SetFilter(filter.clone(), proc, tag)
+            });
+        }
+    }
+
+    fn flush(&self) {
+        for subscriber in &self.subscribers {
+            request(subscriber, Message::Flush);
         }
     }
 }
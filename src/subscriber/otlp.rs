@@ -0,0 +1,488 @@
+//! Subscriber that exports events to an OpenTelemetry collector.
+//!
+//! Events are converted to [OTLP] log records and sent in batches, as
+//! `ExportLogsServiceRequest` JSON payloads, with `POST` requests to the
+//! `/v1/logs` endpoint of a collector over plain HTTP.
+//!
+//! Batches are sent by a worker process linked to the subscriber process, so
+//! waiting for the collector blocks neither the processes emitting events nor
+//! the subscriber process.
+//!
+//! A batch is sent when it holds enough records, when it reaches its maximum
+//! age, or when the subscriber is [flushed](crate::flush).
+//!
+//! [OTLP]: https://opentelemetry.io/docs/specs/otlp/
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{filter::EnvFilter, Event, Level, LevelFilter, Metadata, SpanId, Value};
+
+use super::{
+    batch::{Batcher, Batching, Export, ExportError},
+    http::HttpClient,
+    Interest, Subscriber,
+};
+
+const DEFAULT_MAX_BATCH_SIZE: usize = 512;
+const DEFAULT_MAX_BATCH_AGE: Duration = Duration::from_secs(5);
+
+/// A subscriber exporting events to an OpenTelemetry collector with OTLP/HTTP.
+///
+/// Every event becomes a log record with:
+/// - its level as the severity number and text,
+/// - its message as the body,
+/// - its file, line and module path as the `code.filepath`, `code.lineno`
+///   and `code.namespace` attributes, and its target as `target`,
+/// - the fields of its spans, its context and its fields as attributes,
+/// - the innermost span as the span id, and the root span of its trace, which
+///   may have been created in another process, as the trace id.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{subscriber::otlp::OtlpSubscriber, LevelFilter};
+///
+/// lunatic_log::init(
+///     OtlpSubscriber::new("http://127.0.0.1:4318/v1/logs", LevelFilter::Info)
+///         .with_service_name("my-app"),
+/// );
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct OtlpSubscriber {
+    filter: EnvFilter,
+    batcher: Batcher<Exporter>,
+}
+
+/// Exports batches of log records, serialized as JSON, to a collector.
+#[derive(Clone, Serialize, Deserialize)]
+struct Exporter {
+    client: HttpClient,
+    resource: Vec<(String, Value)>,
+}
+
+impl OtlpSubscriber {
+    /// Creates an instance of [`OtlpSubscriber`] exporting to `endpoint`.
+    ///
+    /// The endpoint is an `http://` URL, with the path defaulting to `/v1/logs`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the URL has another scheme, such as `https://`.
+    pub fn new(endpoint: impl AsRef<str>, level_filter: LevelFilter) -> Self {
+        let exporter = Exporter {
+            client: HttpClient::new(endpoint.as_ref(), "/v1/logs"),
+            resource: vec![("service.name".to_string(), "lunatic".into())],
+        };
+        let batching = Batching {
+            max_size: DEFAULT_MAX_BATCH_SIZE,
+            max_age: DEFAULT_MAX_BATCH_AGE,
+            max_retries: 0,
+            initial_backoff: Duration::ZERO,
+        };
+        OtlpSubscriber {
+            filter: level_filter.into(),
+            batcher: Batcher::new(exporter, batching),
+        }
+    }
+
+    /// Filter logs with an [`EnvFilter`], replacing the level filter.
+    pub fn with_env_filter(mut self, filter: EnvFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the `service.name` resource attribute, `lunatic` by default.
+    pub fn with_service_name(self, service_name: impl Into<String>) -> Self {
+        self.with_resource_attribute("service.name", service_name.into())
+    }
+
+    /// Adds an attribute describing the resource producing the logs, such as
+    /// `service.version` or `deployment.environment`.
+    pub fn with_resource_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<Value>,
+    ) -> Self {
+        let key = key.into();
+        let value = value.into();
+        let resource = &mut self.batcher.exporter_mut().resource;
+        match resource.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => resource.push((key, value)),
+        }
+        self
+    }
+
+    /// Adds a header sent with every request, for example for authentication.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let client = &mut self.batcher.exporter_mut().client;
+        client.insert_header(name.into(), value.into());
+        self
+    }
+
+    /// Sends a batch once it holds this many records, 512 by default.
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.batcher.batching_mut().max_size = max_batch_size.max(1);
+        self
+    }
+
+    /// Sends a batch this long after its first record was added, 5 seconds by
+    /// default.
+    pub fn with_max_batch_age(mut self, max_batch_age: Duration) -> Self {
+        self.batcher.batching_mut().max_age = max_batch_age;
+        self
+    }
+
+    /// Returns a log record, serialized as JSON.
+    fn log_record(&self, event: &Event) -> String {
+        let metadata = event.metadata();
+        let timestamp = DateTime::<Utc>::from(event.timestamp())
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_string();
+        let observed = Utc::now()
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_string();
+        let mut attributes = vec![attribute("target", &Value::from(metadata.target()))];
+        if let Some(file) = metadata.file() {
+            attributes.push(attribute("code.filepath", &Value::from(file)));
+        }
+        if let Some(line) = metadata.line() {
+            attributes.push(attribute("code.lineno", &Value::from(line)));
+        }
+        if let Some(module_path) = metadata.module_path() {
+            attributes.push(attribute("code.namespace", &Value::from(module_path)));
+        }
+        let span_fields = event.spans().iter().flat_map(|span| span.fields());
+        for (name, value) in span_fields.chain(event.context()).chain(event.fields()) {
+            attributes.push(attribute(name, value));
+        }
+
+        let mut record = json!({
+            "timeUnixNano": timestamp,
+            "observedTimeUnixNano": observed,
+            "severityNumber": severity_number(metadata.level()),
+            "severityText": metadata.level().as_str(),
+            "body": { "stringValue": event.message() },
+            "attributes": attributes,
+        });
+        if let Some(innermost) = event.spans().last() {
+            record["traceId"] = trace_id_hex(innermost.trace_id()).into();
+            record["spanId"] = span_id_hex(innermost.id()).into();
+        }
+        record.to_string()
+    }
+}
+
+impl Export for Exporter {
+    type Item = String;
+
+    const FAILURE: &'static str = "failed to export logs to";
+
+    fn url(&self) -> String {
+        self.client.url()
+    }
+
+    fn export(&self, records: Vec<String>) -> Result<(), ExportError<String>> {
+        let resource: Vec<_> = self
+            .resource
+            .iter()
+            .map(|(key, value)| attribute(key, value))
+            .collect();
+        let resource = json!({ "attributes": resource });
+        let scope = json!({
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        // The records are already serialized, so the request is put together
+        // around them.
+        let request = format!(
+            r#"{{"resourceLogs":[{{"resource":{resource},"scopeLogs":[{{"scope":{scope},"logRecords":[{}]}}]}}]}}"#,
+            records.join(",")
+        );
+        match self.client.post("application/json", request.as_bytes()) {
+            Ok(response) if response.is_success() => Ok(()),
+            Ok(response) => Err(ExportError::discard(format!(
+                "status {}: {}",
+                response.status,
+                String::from_utf8_lossy(&response.body).trim()
+            ))),
+            Err(err) => Err(ExportError::discard(err.to_string())),
+        }
+    }
+}
+
+impl Subscriber for OtlpSubscriber {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn event(&self, event: &Event) {
+        self.batcher.push(self.log_record(event));
+    }
+
+    fn interest(&self) -> Interest {
+        self.filter.interest()
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.filter = filter;
+    }
+
+    fn flush(&self) {
+        self.batcher.flush();
+    }
+}
+
+/// Returns the OpenTelemetry severity number of a level.
+fn severity_number(level: &Level) -> u8 {
+    match level {
+        Level::Trace => 1,
+        Level::Debug => 5,
+        Level::Info => 9,
+        Level::Warn => 13,
+        Level::Error => 17,
+    }
+}
+
+/// Returns a 16 byte trace id.
+fn trace_id_hex(trace_id: u128) -> String {
+    format!("{trace_id:032x}")
+}
+
+/// Returns an 8 byte span id, with the lower 32 bits of the process id of a
+/// span id in its upper half and the lower 32 bits of its counter in its lower
+/// half, which are unique unless a node runs billions of processes or spans.
+fn span_id_hex(id: SpanId) -> String {
+    let id = id.into_u128();
+    let folded = ((id >> 64) as u64).rotate_left(32) ^ id as u64;
+    format!("{folded:016x}")
+}
+
+/// Returns a `KeyValue` attribute, with 64 bit integers encoded as strings as
+/// required by the OTLP JSON encoding.
+fn attribute(key: &str, value: &Value) -> serde_json::Value {
+    let value = match value {
+        Value::Str(s) | Value::Debug(s) | Value::Display(s) => json!({ "stringValue": s }),
+        Value::I64(n) => json!({ "intValue": n.to_string() }),
+        Value::U64(n) => match i64::try_from(*n) {
+            Ok(n) => json!({ "intValue": n.to_string() }),
+            Err(_) => json!({ "stringValue": n.to_string() }),
+        },
+        Value::F64(n) => json!({ "doubleValue": n }),
+        Value::Bool(b) => json!({ "boolValue": b }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    use lunatic::{net::TcpListener, spawn_link, Mailbox, Process, Tag};
+
+    use super::*;
+    use crate::Span;
+
+    /// Starts a stand-in for a collector, which answers every request with
+    /// `200 OK` and sends its body to the current process with `tag`. Returns
+    /// the endpoint of the collector.
+    fn collector(tag: Tag) -> String {
+        let parent = Process::<String>::this();
+        spawn_link!(|parent, tag, _mailbox: Mailbox<()>| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            parent.tag_send(tag, listener.local_addr().unwrap().to_string());
+            while let Ok((stream, _)) = listener.accept() {
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    if let Some(len) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        content_length = len.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                parent.tag_send(tag, String::from_utf8(body).unwrap());
+                let mut stream = reader.into_inner();
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                    .unwrap();
+            }
+        });
+        format!("http://{}/v1/logs", receive(tag))
+    }
+
+    fn receive(tag: Tag) -> String {
+        // Safety: only the messages of the collector carrying `tag` are
+        // received, which are always a `String`.
+        let mailbox: Mailbox<String> = unsafe { Mailbox::new() };
+        mailbox.tag_receive(&[tag])
+    }
+
+    fn span(name: &str, parent: Option<SpanId>) -> Span {
+        let metadata = Metadata::new(
+            name.to_string(),
+            "my_app".to_string(),
+            Level::Info,
+            vec!["request_id".to_string()],
+            None,
+            None,
+            None,
+        );
+        Span::new(metadata, vec![Value::from(7u64)], parent)
+    }
+
+    fn event(spans: &[&Span]) -> Event {
+        let metadata = Metadata::new(
+            "event".to_string(),
+            "my_app".to_string(),
+            Level::Warn,
+            vec!["count".to_string(), "big".to_string()],
+            Some("my_app::handler".to_string()),
+            Some("src/handler.rs".to_string()),
+            Some(42),
+        );
+        let values = vec![Value::from(42i64), Value::from(u64::MAX)];
+        let mut event = Event::new("Request took 1200ms".to_string(), metadata, values);
+        event.spans = spans.iter().map(|span| span.context().clone()).collect();
+        event
+    }
+
+    fn attribute_value<'a>(
+        attributes: &'a serde_json::Value,
+        key: &str,
+    ) -> Option<&'a serde_json::Value> {
+        attributes
+            .as_array()?
+            .iter()
+            .find(|attribute| attribute["key"] == key)
+            .map(|attribute| &attribute["value"])
+    }
+
+    #[test]
+    fn exports_log_records() {
+        let tag = Tag::new();
+        let endpoint = collector(tag);
+        let subscriber = OtlpSubscriber::new(endpoint, LevelFilter::Info)
+            .with_service_name("my-app")
+            .with_resource_attribute("deployment.environment", "test");
+
+        // The root span is usually created in another process, which sent its id.
+        let root = span("request", None);
+        let child = span("handler", Some(root.id()));
+        subscriber.event(&event(&[&child]));
+        subscriber.flush();
+
+        let request: serde_json::Value = serde_json::from_str(&receive(tag)).unwrap();
+        let resource_logs = &request["resourceLogs"][0];
+        let resource = &resource_logs["resource"]["attributes"];
+        assert_eq!(
+            attribute_value(resource, "service.name"),
+            Some(&json!({ "stringValue": "my-app" }))
+        );
+        assert_eq!(
+            attribute_value(resource, "deployment.environment"),
+            Some(&json!({ "stringValue": "test" }))
+        );
+        let scope_logs = &resource_logs["scopeLogs"][0];
+        assert_eq!(scope_logs["scope"]["name"], env!("CARGO_PKG_NAME"));
+
+        let records = scope_logs["logRecords"].as_array().unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record["severityNumber"], 13);
+        assert_eq!(record["severityText"], "WARN");
+        assert_eq!(
+            record["body"],
+            json!({ "stringValue": "Request took 1200ms" })
+        );
+        assert!(record["timeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse::<u64>()
+            .is_ok());
+        assert!(record["observedTimeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse::<u64>()
+            .is_ok());
+
+        let attributes = &record["attributes"];
+        assert_eq!(
+            attribute_value(attributes, "count"),
+            Some(&json!({ "intValue": "42" }))
+        );
+        assert_eq!(
+            attribute_value(attributes, "big"),
+            Some(&json!({ "stringValue": u64::MAX.to_string() }))
+        );
+        assert_eq!(
+            attribute_value(attributes, "request_id"),
+            Some(&json!({ "intValue": "7" }))
+        );
+        assert_eq!(
+            attribute_value(attributes, "code.lineno"),
+            Some(&json!({ "intValue": "42" }))
+        );
+        assert_eq!(
+            attribute_value(attributes, "code.filepath"),
+            Some(&json!({ "stringValue": "src/handler.rs" }))
+        );
+
+        let trace_id = record["traceId"].as_str().unwrap();
+        assert_eq!(trace_id, format!("{:032x}", root.id().into_u128()));
+        let span_id = record["spanId"].as_str().unwrap();
+        assert_eq!(span_id.len(), 16);
+        assert!(span_id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(span_id, span_id_hex(child.id()));
+    }
+
+    #[test]
+    fn inherits_trace_id_from_remote_parents() {
+        let root = span("request", None);
+        let child = span("handler", Some(root.id()));
+        let grandchild = span("query", Some(child.id()));
+        assert_eq!(root.context().trace_id(), root.id().into_u128());
+        assert_eq!(grandchild.context().trace_id(), root.id().into_u128());
+
+        let subscriber = OtlpSubscriber::new("http://127.0.0.1:4318", LevelFilter::Info);
+        let record: serde_json::Value =
+            serde_json::from_str(&subscriber.log_record(&event(&[&grandchild]))).unwrap();
+        assert_eq!(record["traceId"], trace_id_hex(root.id().into_u128()));
+        assert_eq!(record["spanId"], span_id_hex(grandchild.id()));
+    }
+
+    #[test]
+    fn omits_trace_context_outside_spans() {
+        let subscriber = OtlpSubscriber::new("http://127.0.0.1:4318", LevelFilter::Info);
+        let record: serde_json::Value =
+            serde_json::from_str(&subscriber.log_record(&event(&[]))).unwrap();
+        assert!(record.get("traceId").is_none());
+        assert!(record.get("spanId").is_none());
+    }
+
+    #[test]
+    fn folds_span_ids_into_eight_bytes() {
+        let id = SpanId::from_u128((0x1234_5678_9abc_def0 << 64) | 0x0000_0000_0000_0003);
+        assert_eq!(span_id_hex(id), "9abcdef01234567b");
+    }
+
+    #[test]
+    fn maps_levels_to_severity_numbers() {
+        assert_eq!(severity_number(&Level::Trace), 1);
+        assert_eq!(severity_number(&Level::Debug), 5);
+        assert_eq!(severity_number(&Level::Info), 9);
+        assert_eq!(severity_number(&Level::Warn), 13);
+        assert_eq!(severity_number(&Level::Error), 17);
+    }
+}
@@ -0,0 +1,415 @@
+//! Subscriber that sends events to a syslog server.
+//!
+//! Messages are formatted as [RFC 5424] or [RFC 3164] and sent over UDP, one
+//! message per datagram, or over TCP with octet-counting framing as described
+//! in [RFC 6587].
+//!
+//! Sending over a Unix datagram socket, such as `/dev/log`, is not supported:
+//! lunatic only gives processes TCP, UDP and TLS sockets. To log to the local
+//! syslog daemon, enable its UDP or TCP input, for example `imudp` on
+//! `127.0.0.1:514` with rsyslog.
+//!
+//! [RFC 5424]: https://www.rfc-editor.org/rfc/rfc5424
+//! [RFC 3164]: https://www.rfc-editor.org/rfc/rfc3164
+//! [RFC 6587]: https://www.rfc-editor.org/rfc/rfc6587#section-3.4.1
+
+use std::{env, fmt::Write};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{filter::EnvFilter, Event, Level, LevelFilter, Metadata};
+
+use super::{
+    transport::{Connection, Protocol},
+    Interest, Subscriber,
+};
+
+/// The SD-ID of the structured data element holding event fields, using the
+/// example enterprise number reserved by RFC 5424.
+const DEFAULT_SD_ID: &str = "fields@32473";
+
+/// A subscriber sending events to a syslog server.
+///
+/// # Example
+///
+/// ```
+/// use lunatic_log::{
+///     subscriber::syslog::{Facility, SyslogSubscriber},
+///     LevelFilter,
+/// };
+///
+/// lunatic_log::init(
+///     SyslogSubscriber::udp("127.0.0.1:514", LevelFilter::Info)
+///         .with_facility(Facility::Local0)
+///         .with_app_name("my-app"),
+/// );
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct SyslogSubscriber {
+    filter: EnvFilter,
+    format: Format,
+    facility: Facility,
+    app_name: String,
+    hostname: String,
+    sd_id: String,
+    connection: Connection,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Format {
+    Rfc5424,
+    Rfc3164,
+}
+
+impl SyslogSubscriber {
+    fn new(addr: String, protocol: Protocol, level_filter: LevelFilter) -> Self {
+        SyslogSubscriber {
+            filter: level_filter.into(),
+            format: Format::Rfc5424,
+            facility: Facility::User,
+            app_name: "lunatic".to_string(),
+            hostname: env::var("HOSTNAME").unwrap_or_else(|_| "-".to_string()),
+            sd_id: DEFAULT_SD_ID.to_string(),
+            connection: Connection::new(addr, protocol),
+        }
+    }
+
+    /// Creates an instance of [`SyslogSubscriber`] sending to `addr` over UDP.
+    pub fn udp(addr: impl Into<String>, level_filter: LevelFilter) -> Self {
+        SyslogSubscriber::new(addr.into(), Protocol::Udp, level_filter)
+    }
+
+    /// Creates an instance of [`SyslogSubscriber`] sending to `addr` over TCP.
+    pub fn tcp(addr: impl Into<String>, level_filter: LevelFilter) -> Self {
+        SyslogSubscriber::new(addr.into(), Protocol::Tcp, level_filter)
+    }
+
+    /// Formats messages as RFC 3164, the BSD syslog format, instead of RFC 5424.
+    ///
+    /// Fields are appended to the message as `key=value`, since RFC 3164 has
+    /// no structured data.
+    pub fn rfc3164(mut self) -> Self {
+        self.format = Format::Rfc3164;
+        self
+    }
+
+    /// Filter logs with an [`EnvFilter`], replacing the level filter.
+    pub fn with_env_filter(mut self, filter: EnvFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the facility of the messages, [`Facility::User`] by default.
+    pub fn with_facility(mut self, facility: Facility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    /// Sets the APP-NAME of the messages, `lunatic` by default.
+    pub fn with_app_name(mut self, app_name: impl Into<String>) -> Self {
+        self.app_name = app_name.into();
+        self
+    }
+
+    /// Sets the HOSTNAME of the messages.
+    ///
+    /// Defaults to the `HOSTNAME` environment variable, or `-` if it isn't set.
+    pub fn with_hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = hostname.into();
+        self
+    }
+
+    /// Sets the SD-ID of the structured data element holding the fields of
+    /// events, `fields@32473` by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sd_id` is not a valid SD-ID: 1 to 32 printable ASCII
+    /// characters, except `=`, space, `]` and `"`.
+    pub fn with_sd_id(mut self, sd_id: impl Into<String>) -> Self {
+        let sd_id = sd_id.into();
+        let valid = (1..=32).contains(&sd_id.len())
+            && sd_id
+                .chars()
+                .all(|c| c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"'));
+        assert!(valid, "invalid syslog SD-ID `{sd_id}`");
+        self.sd_id = sd_id;
+        self
+    }
+
+    fn priority(&self, level: &Level) -> u8 {
+        self.facility as u8 * 8 + severity(level)
+    }
+
+    fn format_rfc5424(&self, event: &Event) -> String {
+        let mut message = format!(
+            "<{}>1 {} {} {} - - ",
+            self.priority(event.metadata().level()),
+            DateTime::<Utc>::from(event.timestamp()).format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+            header_field(&self.hostname, 255),
+            header_field(&self.app_name, 48),
+        );
+        let mut fields = event.context().iter().chain(event.fields()).peekable();
+        if fields.peek().is_some() {
+            write!(message, "[{}", self.sd_id).unwrap();
+            for (name, value) in fields {
+                write!(message, " {}=\"", param_name(name)).unwrap();
+                for c in value.to_string().chars() {
+                    if matches!(c, '"' | '\\' | ']') {
+                        message.push('\\');
+                    }
+                    message.push(c);
+                }
+                message.push('"');
+            }
+            message.push(']');
+        } else {
+            message.push('-');
+        }
+        message.push(' ');
+        message.push_str(event.message());
+        message
+    }
+
+    fn format_rfc3164(&self, event: &Event) -> String {
+        let mut message = format!(
+            "<{}>{} {} {}: {}",
+            self.priority(event.metadata().level()),
+            DateTime::<Utc>::from(event.timestamp()).format("%b %e %H:%M:%S"),
+            header_field(&self.hostname, 255),
+            header_field(&self.app_name, 32),
+            event.message(),
+        );
+        for (name, value) in event.context().iter().chain(event.fields()) {
+            write!(message, " {name}={value}").unwrap();
+        }
+        message
+    }
+}
+
+impl Subscriber for SyslogSubscriber {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn event(&self, event: &Event) {
+        let message = match self.format {
+            Format::Rfc5424 => self.format_rfc5424(event),
+            Format::Rfc3164 => self.format_rfc3164(event),
+        };
+        match self.connection.protocol() {
+            Protocol::Udp => self.connection.send(message.as_bytes()),
+            Protocol::Tcp => {
+                let frame = format!("{} {message}", message.len());
+                self.connection.send(frame.as_bytes());
+            }
+        }
+    }
+
+    fn interest(&self) -> Interest {
+        self.filter.interest()
+    }
+
+    fn set_filter(&mut self, filter: EnvFilter) {
+        self.filter = filter;
+    }
+
+    fn flush(&self) {
+        self.connection.flush();
+    }
+}
+
+/// The syslog facility of a message.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Facility {
+    Kern = 0,
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    Authpriv = 10,
+    Ftp = 11,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// Returns the syslog severity of a level.
+///
+/// Syslog has no level below debug, so [`Level::Trace`] is also mapped to debug.
+pub(crate) fn severity(level: &Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Returns a header field made of printable ASCII, or `-` if it is empty.
+fn header_field(value: &str, max_len: usize) -> String {
+    let value: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+    if value.is_empty() {
+        "-".to_string()
+    } else {
+        value
+    }
+}
+
+/// Returns a valid SD-PARAM name, replacing invalid characters with `_`.
+fn param_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '=' | ']' | '"' => '_',
+            c if c.is_ascii_graphic() => c,
+            _ => '_',
+        })
+        .take(32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use chrono::TimeZone;
+    use lunatic::net::{TcpListener, UdpSocket};
+
+    use super::*;
+
+    fn event(level: Level, message: &str, fields: Vec<(&str, &str)>) -> Event {
+        let (names, values) = fields
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.into()))
+            .unzip();
+        let metadata = Metadata::new(
+            "event".to_string(),
+            "my_app".to_string(),
+            level,
+            names,
+            None,
+            None,
+            None,
+        );
+        let mut event = Event::new(message.to_string(), metadata, values);
+        let timestamp = Utc.with_ymd_and_hms(2024, 5, 1, 13, 4, 5).unwrap();
+        event.timestamp = Some(timestamp.into());
+        event
+    }
+
+    fn subscriber(addr: &str, protocol: Protocol) -> SyslogSubscriber {
+        SyslogSubscriber::new(addr.to_string(), protocol, LevelFilter::Info)
+            .with_hostname("web-1")
+            .with_app_name("my-app")
+    }
+
+    #[test]
+    fn formats_rfc5424_header() {
+        let subscriber = subscriber("127.0.0.1:514", Protocol::Udp);
+        assert_eq!(
+            subscriber.format_rfc5424(&event(Level::Info, "Hello", vec![])),
+            "<14>1 2024-05-01T13:04:05.000000Z web-1 my-app - - - Hello"
+        );
+
+        let subscriber = subscriber
+            .with_facility(Facility::Local0)
+            .with_hostname("web 1\n")
+            .with_app_name("");
+        assert_eq!(
+            subscriber.format_rfc5424(&event(Level::Error, "Failed", vec![])),
+            "<131>1 2024-05-01T13:04:05.000000Z web1 - - - - Failed"
+        );
+    }
+
+    #[test]
+    fn formats_rfc5424_structured_data() {
+        let subscriber = subscriber("127.0.0.1:514", Protocol::Udp).with_sd_id("app@12345");
+        let event = event(
+            Level::Warn,
+            "Slow",
+            vec![("user id", "alice"), ("path", r#"a "b" \c]"#)],
+        );
+        assert_eq!(
+            subscriber.format_rfc5424(&event),
+            r#"<12>1 2024-05-01T13:04:05.000000Z web-1 my-app - - [app@12345 user_id="alice" path="a \"b\" \\c\]"] Slow"#
+        );
+    }
+
+    #[test]
+    fn formats_rfc3164() {
+        let subscriber = subscriber("127.0.0.1:514", Protocol::Udp).rfc3164();
+        assert_eq!(
+            subscriber.format_rfc3164(&event(Level::Debug, "Hello", vec![("user", "alice")])),
+            "<15>May  1 13:04:05 web-1 my-app: Hello user=alice"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid syslog SD-ID")]
+    fn rejects_sd_id_with_space() {
+        subscriber("127.0.0.1:514", Protocol::Udp).with_sd_id("my fields");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid syslog SD-ID")]
+    fn rejects_sd_id_with_quote() {
+        subscriber("127.0.0.1:514", Protocol::Udp).with_sd_id("fields\"");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid syslog SD-ID")]
+    fn rejects_long_sd_id() {
+        subscriber("127.0.0.1:514", Protocol::Udp).with_sd_id("a".repeat(33));
+    }
+
+    #[test]
+    fn sends_udp_datagrams() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        let subscriber = subscriber(&addr, Protocol::Udp);
+        let event = event(Level::Info, "Hello", vec![]);
+        subscriber.event(&event);
+
+        let mut buf = [0; 1024];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], subscriber.format_rfc5424(&event).as_bytes());
+    }
+
+    #[test]
+    fn frames_tcp_messages_with_octet_counting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let subscriber = subscriber(&addr, Protocol::Tcp);
+        let first = event(Level::Info, "Hello", vec![]);
+        let second = event(Level::Info, "Hello again", vec![("user", "alice")]);
+        subscriber.event(&first);
+        subscriber.event(&second);
+        subscriber.flush();
+
+        let (first, second) = (
+            subscriber.format_rfc5424(&first),
+            subscriber.format_rfc5424(&second),
+        );
+        let expected = format!("{} {first}{} {second}", first.len(), second.len());
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = vec![0; expected.len()];
+        stream.read_exact(&mut received).unwrap();
+        assert_eq!(String::from_utf8(received).unwrap(), expected);
+    }
+}
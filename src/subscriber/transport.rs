@@ -0,0 +1,107 @@
+//! Lazily opened network connections, shared by the network subscribers.
+
+use std::{
+    cell::{Cell, RefCell},
+    io::{self, Write},
+    time::Duration,
+};
+
+use lunatic::net::{TcpStream, UdpSocket};
+use serde::{Deserialize, Serialize};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A UDP or TCP connection to a log server.
+///
+/// The socket is opened by the subscriber process when the first message is
+/// sent. A TCP connection that fails is reopened for the next message.
+/// Connecting times out after 10 seconds, and writing after 30 seconds, so
+/// that an unresponsive server doesn't stall the subscriber process.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Connection {
+    addr: String,
+    protocol: Protocol,
+    #[serde(skip)]
+    socket: RefCell<Option<Socket>>,
+    #[serde(skip)]
+    failed: Cell<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Protocol {
+    Udp,
+    Tcp,
+}
+
+#[derive(Debug)]
+enum Socket {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+impl Connection {
+    pub(crate) fn new(addr: String, protocol: Protocol) -> Self {
+        Connection {
+            addr,
+            protocol,
+            socket: RefCell::new(None),
+            failed: Cell::new(false),
+        }
+    }
+
+    pub(crate) fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Sends a datagram over UDP, or writes the bytes to the TCP stream.
+    ///
+    /// Errors are printed to stderr, once for consecutive failures.
+    pub(crate) fn send(&self, bytes: &[u8]) {
+        let mut result = self.try_send(bytes);
+        if result.is_err() && self.protocol == Protocol::Tcp {
+            // The server may have closed the connection, so retry once with a new one.
+            result = self.try_send(bytes);
+        }
+        match result {
+            Ok(()) => self.failed.set(false),
+            Err(err) => {
+                if !self.failed.replace(true) {
+                    eprintln!("lunatic-log: failed to send to {}: {err}", self.addr);
+                }
+            }
+        }
+    }
+
+    fn try_send(&self, bytes: &[u8]) -> io::Result<()> {
+        let mut socket = self.socket.borrow_mut();
+        if socket.is_none() {
+            *socket = Some(match self.protocol {
+                Protocol::Udp => {
+                    let udp = UdpSocket::bind("0.0.0.0:0")?;
+                    udp.connect(self.addr.as_str())?;
+                    Socket::Udp(udp)
+                }
+                Protocol::Tcp => {
+                    let tcp = TcpStream::connect_timeout(self.addr.as_str(), CONNECT_TIMEOUT)?;
+                    tcp.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    Socket::Tcp(tcp)
+                }
+            });
+        }
+        let result = match socket.as_mut().unwrap() {
+            Socket::Udp(udp) => udp.send(bytes).map(|_| ()),
+            Socket::Tcp(tcp) => tcp.write_all(bytes),
+        };
+        if result.is_err() {
+            *socket = None;
+        }
+        result
+    }
+
+    pub(crate) fn flush(&self) {
+        if let Some(Socket::Tcp(tcp)) = &mut *self.socket.borrow_mut() {
+            let _ = tcp.flush();
+        }
+    }
+}
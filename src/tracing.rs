@@ -0,0 +1,287 @@
+//! Bridge from the [`tracing`](::tracing) crate.
+//!
+//! [`TracingSubscriber`] implements [`tracing::Subscriber`](::tracing::Subscriber),
+//! forwarding the events and spans of crates instrumented with `tracing` to
+//! the subscriber process, alongside the events of `lunatic-log`. Tracing
+//! spans become [`Span`]s, so events of both crates carry them.
+//!
+//! `tracing` stores its default subscriber in a static, and every lunatic
+//! process has its own memory, so the bridge needs to be installed with
+//! [`install`] in each process that uses `tracing`.
+//!
+//! Values recorded on a span after it was created are not forwarded, because
+//! the span was already sent to the subscriber process.
+//!
+//! This module requires the `tracing` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use lunatic::spawn_link;
+//! use lunatic_log::{subscriber::fmt::FmtSubscriber, LevelFilter};
+//!
+//! # fn main() {
+//! lunatic_log::init(FmtSubscriber::new(LevelFilter::Info));
+//! lunatic_log::tracing::install().unwrap();
+//! tracing::info!(user = "alice", "Hello from tracing");
+//!
+//! spawn_link!(|_mailbox: Mailbox<()>| {
+//!     lunatic_log::tracing::install().unwrap();
+//!     tracing::info!("Hello from another process");
+//! });
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+};
+
+use ::tracing::{
+    dispatcher::SetGlobalDefaultError,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    subscriber::Interest,
+};
+
+use crate::{Event, Level, LevelFilter, Metadata, Span, Value, STATIC_MAX_LEVEL};
+
+/// Installs a default [`TracingSubscriber`] as the subscriber of the `tracing`
+/// crate in the current process.
+///
+/// Returns an error if a subscriber was already set in this process.
+pub fn install() -> Result<(), SetGlobalDefaultError> {
+    ::tracing::subscriber::set_global_default(TracingSubscriber::new())
+}
+
+/// A [`tracing::Subscriber`](::tracing::Subscriber) forwarding events and
+/// spans to the subscriber process.
+///
+/// Fields are recorded as fields of the events and spans, and the `message`
+/// field as the message of events.
+#[derive(Debug, Default)]
+pub struct TracingSubscriber {
+    fields_in_message: bool,
+    /// The spans, by the ids handed to `tracing`.
+    spans: Mutex<HashMap<u64, SpanRef>>,
+    next_id: AtomicU64,
+}
+
+/// A span, with the number of handles to it held by `tracing`.
+#[derive(Debug)]
+struct SpanRef {
+    span: Span,
+    count: usize,
+}
+
+impl TracingSubscriber {
+    /// Creates an instance of [`TracingSubscriber`].
+    pub fn new() -> Self {
+        TracingSubscriber::default()
+    }
+
+    /// Appends the fields of events to their message as `key=value` pairs,
+    /// instead of recording them as fields.
+    ///
+    /// This is useful with subscribers printing only the message of events.
+    pub fn with_fields_in_message(mut self, fields_in_message: bool) -> Self {
+        self.fields_in_message = fields_in_message;
+        self
+    }
+
+    fn spans(&self) -> MutexGuard<'_, HashMap<u64, SpanRef>> {
+        self.spans.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn metadata(metadata: &::tracing::Metadata<'_>, fields: Vec<String>) -> Metadata {
+        Metadata::new(
+            metadata.name().to_string(),
+            metadata.target().to_string(),
+            level_from_tracing(metadata.level()),
+            fields,
+            metadata.module_path().map(str::to_string),
+            metadata.file().map(str::to_string),
+            metadata.line(),
+        )
+    }
+}
+
+impl ::tracing::Subscriber for TracingSubscriber {
+    fn register_callsite(&self, _metadata: &'static ::tracing::Metadata<'static>) -> Interest {
+        // The interest of the subscriber process can change at runtime, so
+        // `enabled` is asked every time.
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &::tracing::Metadata<'_>) -> bool {
+        let level = level_from_tracing(metadata.level());
+        level <= STATIC_MAX_LEVEL
+            && crate::__lookup_enabled_logging_process(
+                level,
+                metadata.target(),
+                metadata.module_path().unwrap_or(metadata.target()),
+            )
+            .is_some()
+    }
+
+    fn max_level_hint(&self) -> Option<::tracing::level_filters::LevelFilter> {
+        Some(level_filter_to_tracing(STATIC_MAX_LEVEL))
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+        let parent = if let Some(parent) = attributes.parent() {
+            self.spans()
+                .get(&parent.into_u64())
+                .map(|parent| parent.span.id())
+        } else if attributes.is_contextual() {
+            crate::current_span()
+        } else {
+            None
+        };
+        let metadata = TracingSubscriber::metadata(attributes.metadata(), fields.names);
+        let span = Span::new(metadata, fields.values, parent);
+        // `tracing` ids are 64 bits and not zero, so they are counted apart
+        // from span ids.
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.spans().insert(id, SpanRef { span, count: 1 });
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &::tracing::Event<'_>) {
+        let metadata = event.metadata();
+        let level = level_from_tracing(metadata.level());
+        let module_path = metadata.module_path().unwrap_or(metadata.target());
+        let proc =
+            match crate::__lookup_enabled_logging_process(level, metadata.target(), module_path) {
+                Some(proc) => proc,
+                None => return,
+            };
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let mut message = fields.message.take().unwrap_or_default();
+        if self.fields_in_message {
+            for (name, value) in fields.names.drain(..).zip(fields.values.drain(..)) {
+                if !message.is_empty() {
+                    message.push(' ');
+                }
+                write!(message, "{name}={value}").unwrap();
+            }
+        }
+        let metadata = TracingSubscriber::metadata(metadata, fields.names);
+        crate::__send_event(&proc, Event::new(message, metadata, fields.values));
+    }
+
+    fn enter(&self, span: &Id) {
+        if let Some(span) = self.spans().get(&span.into_u64()) {
+            span.span.enter_span();
+        }
+    }
+
+    fn exit(&self, span: &Id) {
+        if let Some(span) = self.spans().get(&span.into_u64()) {
+            span.span.exit_span();
+        }
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some(span) = self.spans().get_mut(&id.into_u64()) {
+            span.count += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let mut spans = self.spans();
+        let closed = match spans.get_mut(&id.into_u64()) {
+            Some(span) => {
+                span.count -= 1;
+                span.count == 0
+            }
+            None => false,
+        };
+        if closed {
+            // Dropping the span notifies the subscriber process.
+            spans.remove(&id.into_u64());
+        }
+        closed
+    }
+}
+
+/// Collects the message and fields of an event or span.
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    names: Vec<String>,
+    values: Vec<Value>,
+}
+
+impl Fields {
+    fn push(&mut self, field: &Field, value: Value) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.names.push(field.name().to_string());
+            self.values.push(value);
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, Value::F64(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, Value::I64(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, Value::U64(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, Value::Bool(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, Value::Str(value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, Value::debug(value));
+    }
+}
+
+fn level_from_tracing(level: &::tracing::Level) -> Level {
+    match *level {
+        ::tracing::Level::ERROR => Level::Error,
+        ::tracing::Level::WARN => Level::Warn,
+        ::tracing::Level::INFO => Level::Info,
+        ::tracing::Level::DEBUG => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+fn level_filter_to_tracing(level_filter: LevelFilter) -> ::tracing::level_filters::LevelFilter {
+    use ::tracing::level_filters::LevelFilter as TracingLevelFilter;
+
+    match level_filter {
+        LevelFilter::Off => TracingLevelFilter::OFF,
+        LevelFilter::Error => TracingLevelFilter::ERROR,
+        LevelFilter::Warn => TracingLevelFilter::WARN,
+        LevelFilter::Info => TracingLevelFilter::INFO,
+        LevelFilter::Debug => TracingLevelFilter::DEBUG,
+        LevelFilter::Trace => TracingLevelFilter::TRACE,
+    }
+}